@@ -0,0 +1,95 @@
+/*
+assets: the files of the input path and the theme that are written to the
+output as they are (images, pdfs, fonts, ...). On large sites copying them
+dominates the build and doubles the disk usage, so they can be linked instead
+(`output.assets` in the config or `--assets`):
+
+    copy      a copy of their own (the default)
+    hardlink  the very same file as in the input path, taking no space. As
+              they share everything, changing the permissions of the output
+              also changes the ones of the input.
+    reflink   a copy sharing its data with the source until either of them is
+              changed, on filesystems supporting it (btrfs, xfs, ...)
+
+Files that can't be linked (e.g. as the output is on another filesystem) are
+copied.
+*/
+
+use std::path::Path;
+use std::io;
+use std::fs;
+
+use serde::Deserialize;
+use serde::de::IntoDeserializer;
+
+use crate::remove_existing;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AssetMode {
+    #[default]
+    Copy,
+    Hardlink,
+    Reflink,
+}
+
+impl AssetMode {
+
+    /// parse the name of a mode, e.g. from the command line
+    pub fn parse(name: &str) -> io::Result<AssetMode> {
+        AssetMode::deserialize(name.into_deserializer())
+            .map_err(|e: serde::de::value::Error| io::Error::new(
+                io::ErrorKind::InvalidInput, format!("invalid asset mode: {}", e)))
+    }
+}
+
+/// Write the asset at `src` to `dst` in the given mode, creating the
+/// directories of `dst` and replacing an existing file, see
+/// `remove_existing`.
+pub fn install(src: &Path, dst: &Path, mode: AssetMode) -> io::Result<()> {
+    if let Some(dst_folder) = dst.parent() {
+        fs::create_dir_all(dst_folder)?;
+    }
+    remove_existing(dst)?;
+
+    let linked = match mode {
+        AssetMode::Copy => false,
+        AssetMode::Hardlink => fs::hard_link(src, dst).is_ok(),
+        AssetMode::Reflink => reflink(src, dst).is_ok(),
+    };
+
+    if !linked {
+        fs::copy(src, dst)?;
+    }
+
+    Ok(())
+}
+
+/// clone the file at `src` to `dst`, removing `dst` again if that failed
+#[cfg(target_os = "linux")]
+fn reflink(src: &Path, dst: &Path) -> io::Result<()> {
+    use std::fs::File;
+    use std::os::unix::io::AsRawFd;
+
+    let src_file = File::open(src)?;
+    let dst_file = File::create(dst)?;
+
+    let result = unsafe {
+        libc::ioctl(dst_file.as_raw_fd(), libc::FICLONE, src_file.as_raw_fd())
+    };
+
+    if result != 0 {
+        let e = io::Error::last_os_error();
+        drop(dst_file);
+        fs::remove_file(dst)?;
+        return Err(e);
+    }
+
+    fs::set_permissions(dst, src_file.metadata()?.permissions())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_src: &Path, _dst: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported,
+        "reflinks are only supported on linux"))
+}
@@ -0,0 +1,384 @@
+/*
+the build cache: a json file next to the output path (`.out.cache.json` for
+`out`) remembering the content hashes of everything the pages and assets of
+the last build were made of. A page is only rendered again if one of its
+dependencies changed:
+
+    - files it reads (its README.md, the template, the settings, the front
+      matter of its subdirectories, files used by directives, ...)
+    - directories it lists (for the navigation, the listing, trees, ...)
+    - modification times it sorts by (the images of a gallery, ...)
+    - values computed from the whole site (the stylesheet, its backlinks, ...)
+
+Assets are only copied again if their content changed. The content of a file
+is only read again if its size or modification time changed, for assets as
+well as for the files pages depend on (e.g. the images of a gallery). A change
+of the config or the version of vokobe invalidates the whole cache.
+*/
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::io::{self, Read, Write};
+use std::fs::{self, File};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::assets::{self, AssetMode};
+use crate::content_hash;
+use crate::settings;
+use crate::tree::{Entry, SiteTree};
+
+/// the suffix of the cache file next to the output path
+pub const CACHE_SUFFIX: &str = ".cache.json";
+
+/// the hash of files and directories that don't exist
+const MISSING: &str = "missing";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    /// the hash of the config and the version of vokobe the cache was
+    /// created with
+    fingerprint: String,
+
+    /// the sources of the copied assets by their path relative to the output
+    /// path
+    assets: BTreeMap<PathBuf, CachedFile>,
+
+    /// the hashes of the dependencies of the rendered pages by their path
+    /// relative to the output path
+    pages: BTreeMap<PathBuf, BTreeMap<String, String>>,
+
+    /// the files the pages depend on by their path
+    files: BTreeMap<PathBuf, CachedFile>,
+}
+
+/// a file and the hash of its content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedFile {
+    hash: String,
+    size: u64,
+    modified: SystemTime,
+}
+
+pub struct Cache {
+    output_path: PathBuf,
+    path: PathBuf,
+
+    /// the cache of the last build
+    old: CacheFile,
+
+    /// the cache of the current build, only containing what is part of it
+    new: CacheFile,
+
+    /// the files the pages of the current build depend on, hashed while
+    /// rendering them in parallel
+    files: Mutex<BTreeMap<PathBuf, CachedFile>>,
+}
+
+impl Cache {
+
+    /// an empty cache for the output path stored at `path`, rebuilding
+    /// everything
+    pub fn empty(output_path: &Path, path: &Path, fingerprint: &str) -> Cache {
+        Cache {
+            output_path: output_path.to_path_buf(),
+            path: path.to_path_buf(),
+            old: CacheFile::default(),
+            new: CacheFile {
+                fingerprint: fingerprint.to_string(),
+                ..CacheFile::default()
+            },
+            files: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// load the cache for the output path from `path`. Returns an empty cache
+    /// if there is none, it can't be read or it was created with another
+    /// fingerprint.
+    pub fn load(output_path: &Path, path: &Path, fingerprint: &str)
+        -> io::Result<Cache> {
+
+        let mut cache = Cache::empty(output_path, path, fingerprint);
+
+        let mut source = String::new();
+        match File::open(&cache.path) {
+            Ok(mut cache_file) => {
+                cache_file.read_to_string(&mut source)?;
+                cache.old = serde_json::from_str::<CacheFile>(&source)
+                    .ok()
+                    .filter(|old| old.fingerprint == fingerprint)
+                    .unwrap_or_default();
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e),
+        };
+
+        Ok(cache)
+    }
+
+    /// copy (or link) the asset at `src` to `dst`, unless it was copied from
+    /// the same content before. Returns the source of the asset to record it.
+    pub fn copy_asset(&self, src: &Path, dst: &Path, mode: AssetMode)
+        -> io::Result<CachedFile> {
+
+        let (asset, unchanged) = self.check_asset(src, dst)?;
+        if !unchanged {
+            assets::install(src, dst, mode)?;
+        }
+
+        Ok(asset)
+    }
+
+    /// the source of the asset at `src` and whether it was copied to `dst`
+    /// from the same content before
+    pub fn check_asset(&self, src: &Path, dst: &Path)
+        -> io::Result<(CachedFile, bool)> {
+
+        let old = self.old.assets.get(self.key(dst));
+        let asset = CachedFile::read(src, old)?;
+
+        let unchanged = old.is_some_and(|old| old.hash == asset.hash)
+            && fs::metadata(dst).is_ok_and(|dst| dst.is_file() && dst.len() == asset.size);
+
+        Ok((asset, unchanged))
+    }
+
+    /// remember the source of the asset copied to `dst`
+    pub fn record_asset(&mut self, dst: &Path, asset: CachedFile) {
+        self.new.assets.insert(self.key(dst).to_path_buf(), asset);
+    }
+
+    /// whether the page at `index_file` was rendered from the same
+    /// dependencies before
+    pub fn is_fresh(&self, tree: &SiteTree, index_file: &Path,
+        dependencies: &Dependencies) -> io::Result<bool> {
+
+        let old = match self.old.pages.get(self.key(index_file)) {
+            Some(old) if index_file.is_file() => old,
+            _ => return Ok(false),
+        };
+
+        // the values are known before rendering, the files and directories
+        // are whatever the last rendering used
+        let values = dependencies.values.lock().unwrap();
+        let old_values = old.keys().filter(|key| key.starts_with("value:")).count();
+        if old_values != values.len() {
+            return Ok(false);
+        }
+
+        for (key, hash) in old {
+            let current = match key.split_once(':') {
+                Some(("value", name)) => values.get(name).map(|v| content_hash(v.as_bytes())),
+                Some(("file", path)) => Some(self.hash_file(Path::new(path))?),
+                Some(("dir", path)) => Some(hash_dir(Path::new(path))?),
+                Some(("modified", path)) => Path::new(path).strip_prefix(tree.in_path()).ok()
+                    .and_then(|rel_path| tree.entry(rel_path))
+                    .map(|entry| hash_time(entry.modified)),
+                _ => None,
+            };
+            if current.as_ref() != Some(hash) {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// keep the dependencies of the fresh page at `index_file` from the last
+    /// build
+    pub fn keep_page(&mut self, index_file: &Path) {
+        if let Some(old) = self.old.pages.get(self.key(index_file)) {
+            self.new.pages.insert(self.key(index_file).to_path_buf(), old.clone());
+        }
+    }
+
+    /// remember the hashes of the dependencies the page at `index_file` was
+    /// rendered from
+    pub fn record_page(&mut self, index_file: &Path,
+        hashes: BTreeMap<String, String>) {
+        self.new.pages.insert(self.key(index_file).to_path_buf(), hashes);
+    }
+
+    /// the hashes of all dependencies of a page
+    pub fn hashes(&self, dependencies: &Dependencies)
+        -> io::Result<BTreeMap<String, String>> {
+
+        let mut hashes = BTreeMap::new();
+
+        for path in dependencies.files.lock().unwrap().iter() {
+            hashes.insert(format!("file:{}", path.display()), self.hash_file(path)?);
+        }
+        for path in dependencies.dirs.lock().unwrap().iter() {
+            hashes.insert(format!("dir:{}", path.display()), hash_dir(path)?);
+        }
+        for (path, modified) in dependencies.modified.lock().unwrap().iter() {
+            hashes.insert(format!("modified:{}", path.display()), hash_time(*modified));
+        }
+        for (name, value) in dependencies.values.lock().unwrap().iter() {
+            hashes.insert(format!("value:{}", name), content_hash(value.as_bytes()));
+        }
+
+        Ok(hashes)
+    }
+
+    /// the hash of the content of the file a page depends on, remembered for
+    /// the next build
+    fn hash_file(&self, path: &Path) -> io::Result<String> {
+        if let Some(file) = self.files.lock().unwrap().get(path) {
+            return Ok(file.hash.clone());
+        }
+
+        // missing files and directories can't be cached
+        match fs::metadata(path) {
+            Ok(metadata) if metadata.is_file() => {},
+            Ok(_) => return Ok(MISSING.to_string()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(MISSING.to_string()),
+            Err(e) => return Err(e),
+        }
+
+        let file = CachedFile::read(path, self.old.files.get(path))?;
+        let hash = file.hash.clone();
+        self.files.lock().unwrap().insert(path.to_path_buf(), file);
+
+        Ok(hash)
+    }
+
+    /// the key of the file at the given path in the output path: its path
+    /// relative to the output path, so that the cache stays valid when the
+    /// output path is moved (see publish.rs)
+    pub fn key<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.output_path).unwrap_or(path)
+    }
+
+    /// write the cache of the current build
+    pub fn save(mut self) -> io::Result<()> {
+        self.new.files = self.files.into_inner().unwrap();
+
+        let json = serde_json::to_string_pretty(&self.new)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        crate::create_file(&self.path)?.write_all(json.as_bytes())
+    }
+}
+
+/// the dependencies of a page, collected while rendering it
+#[derive(Debug, Default)]
+pub struct Dependencies {
+    files: Mutex<BTreeSet<PathBuf>>,
+    dirs: Mutex<BTreeSet<PathBuf>>,
+    modified: Mutex<BTreeMap<PathBuf, SystemTime>>,
+    values: Mutex<BTreeMap<String, String>>,
+}
+
+impl Dependencies {
+
+    /// the page depends on the content of the file at the given path
+    pub fn file(&self, path: &Path) {
+        self.files.lock().unwrap().insert(path.to_path_buf());
+    }
+
+    /// the page depends on the entries of the directory at the given path
+    pub fn dir(&self, path: &Path) {
+        self.dirs.lock().unwrap().insert(path.to_path_buf());
+    }
+
+    /// the page depends on the modification time of the entry, as clamped by
+    /// the site tree
+    pub fn modified(&self, entry: &Entry) {
+        self.modified.lock().unwrap().insert(entry.path.clone(), entry.modified);
+    }
+
+    /// the page depends on a value computed from the whole site
+    pub fn value(&self, name: &str, value: &str) {
+        self.values.lock().unwrap().insert(name.to_string(), value.to_string());
+    }
+
+    /// the page at `raw_path` depends on the directories listed in its
+    /// navigation and listing, the settings of its directory and the front
+    /// matter of its subdirectories
+    pub fn page(&self, tree: &SiteTree, raw_path: &Path) {
+        let mut dir = tree.in_path().to_path_buf();
+        self.dir(&dir);
+        self.file(&dir.join(settings::SETTINGS_FILE));
+        for component in raw_path.components() {
+            dir.push(component);
+            self.dir(&dir);
+            self.file(&dir.join(settings::SETTINGS_FILE));
+        }
+
+        for marker in settings::MARKER_FILES {
+            self.file(&dir.join(marker));
+        }
+
+        for entry in tree.entries(raw_path) {
+            if entry.is_dir {
+                self.file(&entry.path.join("README.md"));
+            }
+        }
+    }
+
+}
+
+impl CachedFile {
+
+    /// the file at the given path. Its content is only hashed if its size or
+    /// modification time changed since it was cached as `old`.
+    fn read(path: &Path, old: Option<&CachedFile>) -> io::Result<CachedFile> {
+        let metadata = fs::metadata(path)?;
+        let size = metadata.len();
+        let modified = metadata.modified()?;
+
+        let hash = match old {
+            Some(old) if old.size == size && old.modified == modified => old.hash.clone(),
+            _ => hash_file(path)?,
+        };
+
+        Ok(CachedFile { hash, size, modified })
+    }
+}
+
+/// the hash of the content of the file at the given path
+fn hash_file(path: &Path) -> io::Result<String> {
+    match fs::read(path) {
+        Ok(data) => Ok(content_hash(&data)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(MISSING.to_string()),
+        Err(e) if e.kind() == io::ErrorKind::IsADirectory => Ok(MISSING.to_string()),
+        Err(e) => Err(e),
+    }
+}
+
+/// the hash of a modification time
+fn hash_time(time: SystemTime) -> String {
+    content_hash(format!("{:?}", time).as_bytes())
+}
+
+/// the hash of the entries of the directory at the given path, including the
+/// content of its .gitignore, which decides which entries are part of the site
+fn hash_dir(path: &Path) -> io::Result<String> {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(MISSING.to_string()),
+        Err(e) => return Err(e),
+    };
+
+    let mut names = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let mut name = path.file_name().unwrap().to_string_lossy().into_owned();
+        if path.is_dir() {
+            name.push('/');
+        }
+        names.push(name);
+    }
+    names.sort();
+
+    let mut listing = names.join("\n");
+    listing.push_str(&hash_file(&path.join(".gitignore"))?);
+
+    Ok(content_hash(listing.as_bytes()))
+}
@@ -0,0 +1,293 @@
+/*
+the site configuration, read from a vokobe.toml in the root of the input path.
+All keys are optional, values given on the command line override the values
+from the file:
+
+    name = "emile.space"
+    base_url = "https://emile.space"
+    author = "emile"
+    language = "en"
+    ignore = ["*.swp", "drafts/"]
+    theme = "../themes/space"
+
+    [features]
+    analytics = false
+    backlinks = true
+
+    [output]
+    path = "../out"
+    inline_style = false
+    assets = "copy"
+    build_time = false
+
+    [footer]
+    copyright = "© 2023 emile"
+
+    [[footer.links]]
+    name = "rss feed"
+    url = "https://chaos.social/@hanemile.rss"
+    icon = "/rss.svg"
+
+    [[footer.webrings]]
+    name = "XXIIVV webring"
+    url = "https://webring.xxiivv.com/#emile"
+    icon = "/webring.svg"
+
+    [extra]
+    mastodon = "@hanemile@chaos.social"
+*/
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::io::{self, Read};
+use std::fs::File;
+
+use serde::Deserialize;
+
+use crate::assets::AssetMode;
+
+/// the name of the config file in the input path
+pub const CONFIG_FILE: &str = "vokobe.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// the name of the site (e.g. emile.space)
+    pub name: Option<String>,
+
+    /// the url the site is hosted at (e.g. https://emile.space)
+    pub base_url: Option<String>,
+
+    /// the author of the site, shown in the footer
+    pub author: Option<String>,
+
+    /// the language of the content, used as `lang` attribute of the pages
+    pub language: Option<String>,
+
+    /// glob patterns of files and directories that are not part of the site.
+    /// Patterns containing a `/` are matched against the path relative to the
+    /// input path, all others against the file name. A trailing `/` only
+    /// matches directories.
+    pub ignore: Vec<String>,
+
+    /// the theme directory providing the templates, stylesheets and other
+    /// assets not present in the input path, relative to the input path
+    pub theme: Option<PathBuf>,
+
+    pub features: Features,
+    pub output: Output,
+    pub footer: Footer,
+
+    /// custom values usable as `{{ extra.<key> }}` in the content
+    pub extra: BTreeMap<String, toml::Value>,
+
+    /// the input path the config belongs to, set when loading the config
+    #[serde(skip)]
+    pub input_path: PathBuf,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Features {
+    /// activate sending analytics to stats.emile.space
+    pub analytics: bool,
+
+    /// append a list of all pages linking to a page to each page
+    pub backlinks: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Output {
+    /// the output path, relative to the input path
+    pub path: Option<PathBuf>,
+
+    /// inline the stylesheet into every page instead of linking it
+    pub inline_style: bool,
+
+    /// how assets are written to the output
+    pub assets: AssetMode,
+
+    /// show the time of the build in the footer. Off by default, as it makes
+    /// the output of every build differ.
+    pub build_time: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Footer {
+    /// the links shown in the footer
+    pub links: Vec<FooterLink>,
+
+    /// the webrings shown in the footer after the links
+    pub webrings: Vec<Webring>,
+
+    /// the line shown instead of the author (e.g. "© 2023 emile")
+    pub copyright: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FooterLink {
+    /// the text of the link, the alt text if an icon is given
+    pub name: String,
+    pub url: String,
+
+    /// the url of an icon shown instead of the name
+    pub icon: Option<String>,
+
+    /// the rel attribute of the link (default: noopener)
+    pub rel: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Webring {
+    pub name: String,
+
+    /// the url of the webring itself
+    pub url: String,
+    pub icon: Option<String>,
+
+    /// the urls of the previous and next site in the ring
+    pub prev: Option<String>,
+    pub next: Option<String>,
+}
+
+impl Config {
+
+    /// load the config from the given file. If the file doesn't exist and
+    /// `required` isn't set, the default config is returned.
+    pub fn load(path: &Path, input_path: &Path, required: bool)
+        -> io::Result<Config> {
+
+        let mut source = String::new();
+        match File::open(path) {
+            Ok(mut config_file) => {
+                config_file.read_to_string(&mut source)?;
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Config {
+                    input_path: input_path.to_path_buf(),
+                    ..Config::default()
+                });
+            },
+            Err(e) => return Err(io::Error::new(e.kind(),
+                format!("could not open config {:?}: {}", path, e))),
+        }
+
+        let mut config: Config = toml::from_str(&source)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), e)))?;
+        config.input_path = input_path.to_path_buf();
+
+        Ok(config)
+    }
+
+    /// the language of the site, defaulting to english
+    pub fn language(&self) -> &str {
+        self.language.as_deref().unwrap_or("en")
+    }
+
+    /// whether the given path (somewhere inside the input path) matches one of
+    /// the ignore patterns
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        let rel_path = match path.strip_prefix(&self.input_path) {
+            Ok(rel_path) => rel_path,
+            Err(_) => return false,
+        };
+        let rel_path = rel_path.to_str().unwrap_or_default();
+        let name = path.file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+
+        self.ignore.iter().any(|pattern| {
+            let (pattern, dir_only) = match pattern.strip_suffix('/') {
+                Some(pattern) => (pattern, true),
+                None => (pattern.as_str(), false),
+            };
+            if dir_only && !path.is_dir() {
+                return false;
+            }
+            match pattern.strip_prefix('/') {
+                Some(pattern) => glob_match(pattern, rel_path),
+                None if pattern.contains('/') => glob_match(pattern, rel_path),
+                None => glob_match(pattern, name),
+            }
+        })
+    }
+}
+
+/// match the text against a glob pattern supporting `*` (any amount of
+/// characters except `/`) and `?` (a single character except `/`)
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    // the positions to continue at if a `*` should match one more character
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            },
+            Some('?') if text[t] != '/' => {
+                p += 1;
+                t += 1;
+            },
+            Some(c) if *c == text[t] => {
+                p += 1;
+                t += 1;
+            },
+            _ => match backtrack {
+                Some((star_p, star_t)) if text[star_t] != '/' => {
+                    backtrack = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                },
+                _ => return false,
+            },
+        }
+    }
+
+    // trailing stars match the empty rest
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn star_matches_within_a_name() {
+        assert!(glob_match("*.swp", ".README.md.swp"));
+        assert!(glob_match("a*b*c", "abxbc"));
+        assert!(!glob_match("*.swp", "README.md"));
+    }
+
+    #[test]
+    fn star_does_not_cross_slashes() {
+        assert!(glob_match("drafts/*", "drafts/post"));
+        assert!(!glob_match("drafts/*", "drafts/post/README.md"));
+        assert!(!glob_match("*.md", "blog/README.md"));
+        assert!(!glob_match("a?b", "a/b"));
+    }
+
+    #[test]
+    fn trailing_star() {
+        assert!(glob_match("draft*", "draft"));
+        assert!(glob_match("draft*", "drafts"));
+        assert!(glob_match("draft**", "draft"));
+        assert!(!glob_match("draft*", "old-draft"));
+    }
+
+    #[test]
+    fn exact_match() {
+        assert!(glob_match("README.md", "README.md"));
+        assert!(!glob_match("README.md", "README.mdx"));
+        assert!(glob_match("", ""));
+    }
+}
@@ -0,0 +1,229 @@
+/*
+stylesheet bundling: the style.css in the input path (and the ones in its
+subdirectories) are bundled into a single stylesheet per page by resolving all
+`@import` statements at build time. A style.css in a subdirectory applies to
+all pages in that subtree, it is appended to the bundle of its parent.
+Stylesheets (and the files they import) missing in the input path are taken
+from the theme.
+*/
+
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::io::{self, Read, Write};
+use std::fs::{self, File};
+
+use crate::content_hash;
+use crate::theme::Theme;
+use crate::tree::SiteTree;
+
+/// the name of the stylesheets in the input path
+pub const STYLE_FILE: &str = "style.css";
+
+/// a bundled stylesheet and how to include it in a page
+#[derive(Debug, Clone)]
+pub struct Stylesheet {
+    /// the content of the bundle
+    pub style: String,
+
+    /// the html including the bundle: a <link> or an inline <style>
+    pub html: String,
+
+    /// the bundle in the output path, None if it is inlined
+    pub path: Option<PathBuf>,
+}
+
+/// bundles the stylesheets of the pages, each bundle is written to the output
+/// only once
+pub struct Stylesheets {
+    theme: Theme,
+    output_path: PathBuf,
+    inline: bool,
+
+    /// the bundles by the list of style.css files they are made of
+    bundles: BTreeMap<Vec<PathBuf>, Stylesheet>,
+}
+
+impl Stylesheets {
+
+    pub fn new(theme: &Theme, output_path: &Path, inline: bool) -> Stylesheets {
+        Stylesheets {
+            theme: theme.clone(),
+            output_path: output_path.to_path_buf(),
+            inline,
+            bundles: BTreeMap::new(),
+        }
+    }
+
+    /// the stylesheet of the page at `raw_path`: the bundle of the style.css
+    /// in the input path and all style.css files in the directories down to
+    /// the page. The pathes are relative to the input path.
+    pub fn for_page(&mut self, tree: &SiteTree, raw_path: &Path)
+        -> io::Result<Stylesheet> {
+
+        let mut dir = PathBuf::new();
+        let mut sources = vec![PathBuf::from(STYLE_FILE)];
+        for component in raw_path.components() {
+            dir.push(component);
+            let style_path = dir.join(STYLE_FILE);
+            if self.theme.has_file(tree, &style_path) {
+                sources.push(style_path);
+            }
+        }
+
+        if let Some(stylesheet) = self.bundles.get(&sources) {
+            return Ok(stylesheet.clone());
+        }
+
+        let mut style = String::new();
+        for source in &sources {
+            style.push_str(&bundle(&self.theme, source, &mut Vec::new())?);
+        }
+
+        // either inline the style into every page or write it once with the
+        // hash of its content in the name, so that browsers can cache it
+        // forever
+        let (html, path) = if self.inline {
+            (format!(r#"<style>
+  {}
+  </style>"#, style), None)
+        } else {
+            let style_name = format!("style.{}.css",
+                &content_hash(style.as_bytes())[..8]);
+            let style_path = self.output_path.join(&style_name);
+            (format!(r#"<link rel="stylesheet" href="/{}">"#, style_name),
+                Some(style_path))
+        };
+
+        let stylesheet = Stylesheet { style, html, path };
+        self.bundles.insert(sources, stylesheet.clone());
+
+        Ok(stylesheet)
+    }
+
+    /// the bundles missing in the output path and their content. The name of
+    /// a bundle changes with its content, so an existing bundle is up to
+    /// date.
+    pub fn unwritten(&self) -> BTreeMap<&Path, &str> {
+        self.bundles.values()
+            .filter_map(|stylesheet| Some((stylesheet.path.as_deref()?,
+                stylesheet.style.as_str())))
+            .filter(|(path, _)| !path.is_file())
+            .collect()
+    }
+
+    /// write the bundles missing in the output path
+    pub fn write(&self) -> io::Result<()> {
+        for (path, style) in self.unwritten() {
+            fs::create_dir_all(&self.output_path)?;
+            crate::create_file(path)?.write_all(style.as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// read the stylesheet at the given path (relative to the input path),
+/// replacing all `@import` statements of local files with the (bundled)
+/// content of the imported file. Absolute imports are relative to the input
+/// path. `stack` contains the files currently being bundled, to detect import
+/// cycles.
+fn bundle(theme: &Theme, rel_path: &Path, stack: &mut Vec<PathBuf>)
+    -> io::Result<String> {
+    let path = theme.resolve(rel_path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!(
+            "could not open stylesheet {:?}: not found in the input path or \
+            the theme", rel_path)))?;
+
+    let mut source = String::new();
+    File::open(&path)
+        .map_err(|e| io::Error::new(e.kind(), format!(
+            "could not open stylesheet {:?}: {}", path, e)))?
+        .read_to_string(&mut source)?;
+
+    stack.push(rel_path.to_path_buf());
+
+    let mut output = String::new();
+    for (line_number, line) in source.split_inclusive('\n').enumerate() {
+        let (target, media) = match parse_import(line) {
+            Some(import) => import,
+            None => {
+                output.push_str(line);
+                continue
+            },
+        };
+
+        // imports from other hosts are left to the browser
+        if target.contains("://") || target.starts_with("//") {
+            output.push_str(line);
+            continue
+        }
+
+        let locate = |msg: String| io::Error::new(io::ErrorKind::InvalidData,
+            format!("{}:{}: {}", path.display(), line_number + 1, msg));
+
+        let import_path = match target.strip_prefix('/') {
+            Some(absolute) => normalize(Path::new(absolute)),
+            None => normalize(&rel_path.parent().unwrap().join(target)),
+        };
+
+        if theme.resolve(&import_path).is_none() {
+            return Err(locate(format!("imported stylesheet {:?} does not exist",
+                import_path)));
+        }
+        if stack.contains(&import_path) {
+            return Err(locate(format!("import cycle, {:?} is already being imported",
+                import_path)));
+        }
+
+        let mut imported = bundle(theme, &import_path, stack)?;
+        if !imported.ends_with('\n') {
+            imported.push('\n');
+        }
+
+        match media {
+            Some(media) => output.push_str(&format!("@media {} {{\n{}}}\n",
+                media, imported)),
+            None => output.push_str(&imported),
+        }
+    }
+
+    stack.pop();
+
+    Ok(output)
+}
+
+/// parse an `@import "file.css" [media];` or `@import url(file.css) [media];`
+/// line into the imported file and the media query
+fn parse_import(line: &str) -> Option<(&str, Option<&str>)> {
+    let rest = line.trim().strip_prefix("@import")?;
+    let rest = rest.trim().strip_suffix(';')?.trim();
+
+    let (target, media) = if let Some(rest) = rest.strip_prefix("url(") {
+        let end = rest.find(')')?;
+        (rest[..end].trim().trim_matches(|c| c == '"' || c == '\''), &rest[end+1..])
+    } else {
+        let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let end = rest[1..].find(quote)? + 1;
+        (&rest[1..end], &rest[end+1..])
+    };
+
+    let media = Some(media.trim()).filter(|m| !m.is_empty());
+
+    Some((target, media))
+}
+
+/// normalize `.` and `..` in a relative path without touching the filesystem,
+/// pathes leaving the input path are kept at its root
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            },
+            Component::Normal(name) => normalized.push(name),
+            _ => (),
+        }
+    }
+    normalized
+}
@@ -0,0 +1,288 @@
+/*
+directives are lines in a README.md of the form
+
+    :::name positional "quoted positional" flag key=value key="quoted value"
+
+that are replaced with generated content. This module parses these lines,
+checks them against the specification of the registered directive and
+dispatches them to the handler writing the content.
+*/
+
+use std::path::Path;
+use std::io::{self, Write};
+
+use crate::cache::Dependencies;
+use crate::links::{self, LinkGraph};
+use crate::tree::SiteTree;
+use crate::{gallery, table};
+
+/// everything a directive handler might need to know about the page it is
+/// rendered on
+pub struct Context<'a> {
+    pub in_path: &'a Path,
+    pub raw_path: &'a Path,
+
+    /// the snapshot of the input path
+    pub tree: &'a SiteTree,
+
+    /// the raw content of the README.md the directive is part of
+    pub readme: &'a str,
+
+    /// the path of the README.md, to locate errors
+    pub readme_path: &'a Path,
+
+    /// the files and directories the page depends on, directives add the
+    /// ones they read
+    pub dependencies: &'a Dependencies,
+
+    pub links: &'a LinkGraph,
+}
+
+/// a parsed and checked directive line
+#[derive(Debug, Default)]
+pub struct Directive {
+    /// the positional arguments
+    pub args: Vec<String>,
+
+    /// the flags given (a subset of the flags in the spec)
+    pub flags: Vec<String>,
+
+    /// the key=value options in the order they were given, keys may occur
+    /// multiple times
+    pub options: Vec<(String, String)>,
+}
+
+impl Directive {
+
+    /// whether the given flag was set
+    pub fn flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|f| f == flag)
+    }
+
+    /// the value of the last occurrence of the given option
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options.iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// the values of all occurrences of the given option
+    pub fn options<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> {
+        self.options.iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+type Handler = fn(&mut dyn Write, &Context, &Directive) -> io::Result<()>;
+
+/// the specification of a directive: the arguments it accepts and the
+/// function writing its content
+struct Spec {
+    name: &'static str,
+
+    /// the minimal and maximal amount of positional arguments
+    args: (usize, usize),
+
+    /// the bare words treated as flags instead of positional arguments
+    flags: &'static [&'static str],
+
+    /// the accepted option keys
+    options: &'static [&'static str],
+
+    handler: Handler,
+}
+
+/// all known directives
+const DIRECTIVES: &[Spec] = &[
+    Spec {
+        name: "tree",
+        args: (0, 0),
+        flags: &["reverse"],
+        options: &[],
+        handler: crate::write_tree,
+    },
+    Spec {
+        name: "toc",
+        args: (0, 0),
+        flags: &[],
+        options: &["depth"],
+        handler: crate::write_toc,
+    },
+    Spec {
+        name: "gallery",
+        args: (0, 1),
+        flags: &["reverse"],
+        options: &["sort"],
+        handler: gallery::write_gallery,
+    },
+    Spec {
+        name: "table",
+        args: (1, 1),
+        flags: &["reverse"],
+        options: &["columns", "sort", "rename"],
+        handler: table::write_table,
+    },
+    Spec {
+        name: "backlinks",
+        args: (0, 0),
+        flags: &[],
+        options: &[],
+        handler: links::write_backlinks,
+    },
+];
+
+/// whether the given line is a directive, i.e. `:::` directly followed by a
+/// name
+pub fn is_directive(line: &str) -> bool {
+    line.strip_prefix(":::")
+        .and_then(|rest| rest.chars().next())
+        .is_some_and(|c| c.is_ascii_alphabetic())
+}
+
+/// Parse the directive in the given line and write its content to the file.
+/// Errors are prefixed with the path of the README.md and the line number.
+pub fn write_directive(file: &mut dyn Write, ctx: &Context, line: &str,
+    line_number: usize) -> io::Result<()> {
+
+    let readme_path = ctx.readme_path;
+    let locate = |e: io::Error| io::Error::new(e.kind(),
+        format!("{}:{}: {}", readme_path.display(), line_number, e));
+
+    let (spec, directive) = parse(line).map_err(locate)?;
+    (spec.handler)(file, ctx, &directive).map_err(locate)
+}
+
+/// parse the directive line and check it against the spec of the directive
+fn parse(line: &str) -> io::Result<(&'static Spec, Directive)> {
+    let mut words = split_words(line.strip_prefix(":::").unwrap_or(line))?
+        .into_iter();
+
+    let name = words.next().unwrap_or_default();
+
+    let spec = DIRECTIVES.iter()
+        .find(|spec| spec.name == name)
+        .ok_or_else(|| invalid_input(format!(
+            "unknown directive `:::{}`, expected one of {}", name,
+            DIRECTIVES.iter()
+                .map(|spec| spec.name)
+                .collect::<Vec<_>>()
+                .join(", "))))?;
+
+    let mut directive = Directive::default();
+
+    for word in words {
+        if let Some((key, value)) = word.split_once('=') {
+            if !spec.options.contains(&key) {
+                return Err(invalid_input(format!(
+                    "unknown option `{}` for directive `:::{}`", key, spec.name)));
+            }
+            directive.options.push((key.to_string(), value.to_string()));
+        } else if spec.flags.contains(&word.as_str()) {
+            directive.flags.push(word);
+        } else {
+            directive.args.push(word);
+        }
+    }
+
+    let (min, max) = spec.args;
+    if directive.args.len() < min || directive.args.len() > max {
+        let expected = if min == max {
+            format!("{}", min)
+        } else {
+            format!("{} to {}", min, max)
+        };
+        return Err(invalid_input(format!(
+            "directive `:::{}` takes {} arguments, got {}: {:?}",
+            spec.name, expected, directive.args.len(), directive.args)));
+    }
+
+    Ok((spec, directive))
+}
+
+/// split the line into whitespace separated words. Double quotes group words
+/// containing whitespace (`"a b"`, `key="a b"`), a backslash escapes the next
+/// character inside quotes.
+fn split_words(line: &str) -> io::Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut in_quotes = false;
+
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' => in_quotes = false,
+                '\\' => match chars.next() {
+                    Some(c) => word.push(c),
+                    None => break,
+                },
+                _ => word.push(c),
+            }
+            continue
+        }
+
+        match c {
+            '"' => {
+                in_quotes = true;
+                in_word = true;
+            },
+            _ if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            },
+            _ => {
+                word.push(c);
+                in_word = true;
+            },
+        }
+    }
+
+    if in_quotes {
+        return Err(invalid_input("unterminated quote".to_string()));
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+/// the error returned for malformed directives and bad directive arguments
+pub fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_split_at_whitespace() {
+        assert_eq!(split_words("  a  b\tc ").unwrap(), ["a", "b", "c"]);
+        assert!(split_words("   ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn quoted_words() {
+        assert_eq!(split_words(r#"img "a b" key="c d" """#).unwrap(),
+            ["img", "a b", "key=c d", ""]);
+    }
+
+    #[test]
+    fn escaped_words() {
+        assert_eq!(split_words(r#""say \"hi\"" "a\\b""#).unwrap(),
+            [r#"say "hi""#, r"a\b"]);
+    }
+
+    #[test]
+    fn unterminated_quote() {
+        let e = split_words(r#"a "b c"#).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        assert!(split_words(r#""a\"#).is_err());
+    }
+}
@@ -0,0 +1,68 @@
+/*
+front matter: an optional block of `key: value` lines at the very start of a
+README.md, enclosed in `---` lines:
+
+    ---
+    title: Some page
+    description: What this page is about
+    date: 2023-04-01
+    thumbnail: cover.jpg
+    ---
+*/
+
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default, Clone)]
+pub struct FrontMatter {
+    fields: BTreeMap<String, String>,
+}
+
+impl FrontMatter {
+
+    /// the value of the given key, if it is set and not empty
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.get(key)
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    }
+
+    /// all keys and their values
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
+/// split the readme into its front matter and the rest of the content. Also
+/// returns the amount of lines the front matter block took up, so that line
+/// numbers in the content can be mapped back to the file.
+pub fn split(readme: &str) -> (FrontMatter, &str, usize) {
+    let mut front_matter = FrontMatter::default();
+
+    let rest = match readme.strip_prefix("---\n") {
+        Some(rest) => rest,
+        None => return (front_matter, readme, 0),
+    };
+
+    let mut consumed = 1;
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        consumed += 1;
+
+        let line = line.trim_end();
+        if line == "---" {
+            return (front_matter, &rest[offset..], consumed);
+        }
+
+        if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            let value = value.strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            front_matter.fields.insert(key.trim().to_string(), value.to_string());
+        }
+    }
+
+    // no closing `---`, so this wasn't front matter after all
+    (FrontMatter::default(), readme, 0)
+}
@@ -0,0 +1,518 @@
+/*
+the `:::gallery` directive: turn all images in a directory into a grid of
+thumbnails linking to the full size images copied to the output by `main`
+*/
+
+use std::path::{Path, PathBuf};
+use std::io::{self, Read, Seek, Write, BufReader};
+use std::fs::File;
+use std::time;
+
+use crate::directive::{invalid_input, Context, Directive};
+use crate::escape_html;
+use crate::tree::SiteTree;
+
+/// file extensions considered to be images
+const IMAGE_EXTENSIONS: [&str; 8]
+    = ["jpg", "jpeg", "png", "gif", "webp", "avif", "svg", "bmp"];
+
+/// name of the optional sidecar file containing the captions of the images in
+/// a gallery directory. Each line has the form `image.jpg: some caption`
+pub const CAPTIONS_FILE: &str = "captions.txt";
+
+/// the largest png text chunk read for a caption, larger ones are skipped
+const MAX_TEXT_CHUNK: usize = 1 << 16;
+
+/// the order in which the images are displayed
+#[derive(Debug, PartialEq)]
+enum Order {
+    Name,
+    Date,
+    Caption,
+}
+
+struct Image {
+    name: String,
+    caption: Option<String>,
+    modified: time::SystemTime,
+}
+
+/// Write a gallery of all images in the page directory (or the subdirectory
+/// given as argument) to the file.
+///
+/// Options:
+///   - `sort=name|date|caption`: the order of the images (default: name)
+///   - `reverse`: reverse the order
+pub fn write_gallery(file: &mut dyn Write, ctx: &Context, directive: &Directive)
+    -> io::Result<()> {
+
+    let in_path = ctx.in_path;
+    let raw_path = ctx.raw_path;
+
+    let subdir = directive.args.first()
+        .map(PathBuf::from)
+        .unwrap_or_default();
+
+    let order = match directive.option("sort") {
+        None | Some("name") => Order::Name,
+        Some("date") => Order::Date,
+        Some("caption") => Order::Caption,
+        Some(order) => return Err(invalid_input(format!(
+            "unknown gallery order {:?}, expected one of name, date or \
+            caption", order))),
+    };
+
+    let rel_dir = raw_path.join(&subdir);
+    let gallery_path = Path::new(in_path).join(&rel_dir);
+    if !ctx.tree.is_dir(&rel_dir) {
+        return Err(io::Error::new(io::ErrorKind::NotFound,
+            format!("gallery directory {:?} does not exist or is ignored",
+                gallery_path)));
+    }
+
+    let mut images = read_images(ctx.tree, &rel_dir)?;
+
+    ctx.dependencies.dir(&gallery_path);
+    ctx.dependencies.file(&gallery_path.join(CAPTIONS_FILE));
+    for image in &images {
+        ctx.dependencies.file(&gallery_path.join(&image.name));
+        if order == Order::Date {
+            ctx.dependencies.modified(ctx.tree.entry(&rel_dir.join(&image.name)).unwrap());
+        }
+    }
+
+    match order {
+        Order::Name => images.sort_by(|a, b| a.name.cmp(&b.name)),
+        Order::Date => images.sort_by(|a, b| a.modified.cmp(&b.modified)
+            .then_with(|| a.name.cmp(&b.name))),
+        Order::Caption => images.sort_by(|a, b| a.caption.cmp(&b.caption)
+            .then_with(|| a.name.cmp(&b.name))),
+    }
+
+    if directive.flag("reverse") {
+        images.reverse();
+    }
+
+    // the gallery isn't preformatted text, so close the <pre> block the
+    // readme content is written into and reopen it afterwards
+    file.write_all(r#"</pre>
+<div class="gallery" style="display: flex; flex-wrap: wrap; gap: 1em">"#
+        .as_bytes())?;
+
+    for image in images {
+        let link = Path::new("/").join(raw_path).join(&subdir).join(&image.name);
+        let link = link.to_str().unwrap();
+
+        let alt = escape_html(image.caption.as_deref().unwrap_or(&image.name));
+
+        file.write_all(format!(r#"
+  <figure>
+    <a href="{link}"><img src="{link}" alt="{alt}" loading="lazy" height="150px"/></a>"#,
+            link = link,
+            alt = alt,
+        ).as_bytes())?;
+
+        if let Some(caption) = &image.caption {
+            file.write_all(format!(r#"
+    <figcaption>{}</figcaption>"#, escape_html(caption)).as_bytes())?;
+        }
+
+        file.write_all(r#"
+  </figure>"#.as_bytes())?;
+    }
+
+    file.write_all(r#"
+</div>
+<pre>"#.as_bytes())?;
+
+    Ok(())
+}
+
+/// read all (non-hidden, non-ignored) images in the given directory (relative
+/// to the input path) including their captions
+fn read_images(tree: &SiteTree, rel_dir: &Path) -> io::Result<Vec<Image>> {
+    let captions = parse_captions(tree.captions(rel_dir).unwrap_or_default());
+
+    let mut images = Vec::new();
+
+    for entry in tree.entries(rel_dir) {
+        let path = &entry.path;
+
+        if entry.is_dir || !entry.is_part_of_site() {
+            continue
+        }
+
+        let name = entry.name.clone();
+        if name.starts_with('.') {
+            continue
+        }
+
+        let extension = path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        match extension {
+            Some(e) if IMAGE_EXTENSIONS.contains(&e.as_str()) => {},
+            _ => continue,
+        }
+
+        // prefer the caption from the sidecar file, fall back to the one
+        // embedded in the image itself
+        let caption = match captions.iter().find(|(n, _)| *n == name) {
+            Some((_, caption)) => Some(caption.clone()),
+            None => read_embedded_caption(path)?,
+        };
+
+        images.push(Image {
+            name,
+            caption,
+            modified: entry.modified,
+        });
+    }
+
+    Ok(images)
+}
+
+/// the `image: caption` pairs of the captions file
+fn parse_captions(source: &str) -> Vec<(String, String)> {
+    source.lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, caption)| (name.trim().to_string(), caption.trim().to_string()))
+        .collect()
+}
+
+/// try to extract a caption from the metadata of the image at the given path,
+/// see `embedded_caption`
+fn read_embedded_caption(path: &Path) -> io::Result<Option<String>> {
+    embedded_caption(&mut BufReader::new(File::open(path)?))
+}
+
+/// try to extract a caption from the image metadata: the `Title` or
+/// `Description` text chunk of a png or the exif `ImageDescription` or comment
+/// of a jpeg. Only the headers in front of the image data are read.
+fn embedded_caption(reader: &mut (impl Read + Seek)) -> io::Result<Option<String>> {
+    let mut signature = [0; 8];
+    let caption = if !read_or_eof(reader, &mut signature[..2])? {
+        None
+    } else if signature[..2] == [0xff, 0xd8] {
+        jpeg_caption(reader)?
+    } else if read_or_eof(reader, &mut signature[2..])?
+        && &signature == b"\x89PNG\r\n\x1a\n" {
+        png_caption(reader)?
+    } else {
+        None
+    };
+
+    Ok(caption
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty()))
+}
+
+/// fill the buffer from the reader, false if the file ends before
+fn read_or_eof(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<bool> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// read the next `len` bytes from the reader, None if the file ends before
+fn read_chunk(reader: &mut impl Read, len: usize) -> io::Result<Option<Vec<u8>>> {
+    let mut chunk = vec![0; len];
+    Ok(read_or_eof(reader, &mut chunk)?.then_some(chunk))
+}
+
+/// walk the png chunks (after the signature) looking for a `tEXt` or `iTXt`
+/// chunk with a `Title` or `Description` keyword, up to the image data
+fn png_caption(reader: &mut (impl Read + Seek)) -> io::Result<Option<String>> {
+    let mut description = None;
+
+    let mut header = [0; 8];
+    while read_or_eof(reader, &mut header)? {
+        let len = u32::from_be_bytes(header[..4].try_into().unwrap()) as usize;
+        let kind = &header[4..];
+
+        if kind == b"IDAT" || kind == b"IEND" {
+            break
+        }
+
+        if (kind != b"tEXt" && kind != b"iTXt") || len > MAX_TEXT_CHUNK {
+            // data + crc
+            reader.seek_relative(len as i64 + 4)?;
+            continue
+        }
+
+        let chunk = match read_chunk(reader, len)? {
+            Some(chunk) => chunk,
+            None => break,
+        };
+        reader.seek_relative(4)?;
+
+        if let Some(sep) = chunk.iter().position(|b| *b == 0) {
+            let keyword = &chunk[..sep];
+            let text = match kind {
+                // tEXt chunks are latin-1 encoded
+                b"tEXt" => Some(chunk[sep+1..].iter().map(|b| *b as char).collect()),
+                _ => international_text(&chunk[sep+1..]),
+            };
+            match (keyword, text) {
+                (b"Title", Some(text)) => return Ok(Some(text)),
+                (b"Description", Some(text)) => description = Some(text),
+                _ => {},
+            }
+        }
+    }
+
+    Ok(description)
+}
+
+/// the text of an `iTXt` chunk after its keyword: the compression flag and
+/// method, the language tag and the translated keyword are followed by the
+/// utf-8 text. Compressed text is skipped.
+fn international_text(data: &[u8]) -> Option<String> {
+    if *data.first()? != 0 {
+        return None;
+    }
+
+    let mut fields = data.get(2..)?.splitn(3, |b| *b == 0);
+    let _language = fields.next()?;
+    let _translated_keyword = fields.next()?;
+    Some(String::from_utf8_lossy(fields.next()?).to_string())
+}
+
+/// walk the jpeg segments (after the start of image) looking for an exif
+/// `ImageDescription` or a comment, up to the image data
+fn jpeg_caption(reader: &mut (impl Read + Seek)) -> io::Result<Option<String>> {
+    let mut comment = None;
+
+    let mut header = [0; 4];
+    while read_or_eof(reader, &mut header)? && header[0] == 0xff {
+        let marker = header[1];
+
+        // start of scan, the image data follows
+        if marker == 0xda {
+            break
+        }
+
+        // the length includes its own two bytes
+        let len = (u16::from_be_bytes([header[2], header[3]]) as usize)
+            .saturating_sub(2);
+
+        // only APP1 (exif) and COM segments are read
+        if marker != 0xe1 && marker != 0xfe {
+            reader.seek_relative(len as i64)?;
+            continue
+        }
+
+        let segment = match read_chunk(reader, len)? {
+            Some(segment) => segment,
+            None => break,
+        };
+
+        match marker {
+            0xe1 if segment.starts_with(b"Exif\0\0") => {
+                if let Some(description) = exif_description(&segment[6..]) {
+                    return Ok(Some(description));
+                }
+            },
+            0xfe => comment = Some(String::from_utf8_lossy(&segment).to_string()),
+            _ => {},
+        }
+    }
+
+    Ok(comment)
+}
+
+/// read the `ImageDescription` (0x010e) tag from the first IFD of the tiff
+/// structure embedded in the exif segment
+fn exif_description(tiff: &[u8]) -> Option<String> {
+    let big_endian = match tiff.get(0..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+
+    let u16_at = |pos: usize| -> Option<usize> {
+        let bytes = [*tiff.get(pos)?, *tiff.get(pos+1)?];
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        } as usize)
+    };
+    let u32_at = |pos: usize| -> Option<usize> {
+        let bytes = tiff.get(pos..pos+4)?.try_into().ok()?;
+        Some(if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        } as usize)
+    };
+
+    let ifd = u32_at(4)?;
+    let entries = u16_at(ifd)?;
+
+    for i in 0..entries {
+        let entry = ifd + 2 + i * 12;
+        if u16_at(entry)? != 0x010e {
+            continue
+        }
+
+        // values of up to 4 bytes are stored inline, longer ones at an offset
+        let count = u32_at(entry + 4)?;
+        let value = if count <= 4 {
+            tiff.get(entry+8..entry+8+count)?
+        } else {
+            let offset = u32_at(entry + 8)?;
+            tiff.get(offset..offset+count)?
+        };
+
+        let value = value.split(|b| *b == 0).next()?;
+        return Some(String::from_utf8_lossy(value).to_string());
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    /// a png chunk with a dummy crc
+    fn png_chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend(kind);
+        chunk.extend(data);
+        chunk.extend([0; 4]);
+        chunk
+    }
+
+    fn png(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend(png_chunk(b"IHDR", &[0; 13]));
+        png.extend(chunks.concat());
+        png.extend(png_chunk(b"IEND", &[]));
+        png
+    }
+
+    /// a jpeg with the given segments in front of the image data
+    fn jpeg(segments: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        let mut jpeg = vec![0xff, 0xd8];
+        for (marker, data) in segments {
+            jpeg.extend([0xff, *marker]);
+            jpeg.extend((data.len() as u16 + 2).to_be_bytes());
+            jpeg.extend(data);
+        }
+        jpeg.extend([0xff, 0xda, 0, 2, 1, 2, 3]);
+        jpeg
+    }
+
+    /// an exif segment with an `ImageDescription` longer than 4 bytes, which
+    /// is stored at an offset
+    fn exif(big_endian: bool, description: &str) -> Vec<u8> {
+        let u16_bytes = |n: u16| match big_endian {
+            true => n.to_be_bytes().to_vec(),
+            false => n.to_le_bytes().to_vec(),
+        };
+        let u32_bytes = |n: u32| match big_endian {
+            true => n.to_be_bytes().to_vec(),
+            false => n.to_le_bytes().to_vec(),
+        };
+
+        let mut exif = b"Exif\0\0".to_vec();
+        exif.extend(match big_endian {
+            true => b"MM",
+            false => b"II",
+        });
+        exif.extend(u16_bytes(42));
+        exif.extend(u32_bytes(8));
+
+        // one entry: tag, ascii type, count and offset, then no next IFD
+        exif.extend(u16_bytes(1));
+        exif.extend(u16_bytes(0x010e));
+        exif.extend(u16_bytes(2));
+        exif.extend(u32_bytes(description.len() as u32 + 1));
+        exif.extend(u32_bytes(8 + 2 + 12 + 4));
+        exif.extend(u32_bytes(0));
+
+        exif.extend(description.as_bytes());
+        exif.push(0);
+        exif
+    }
+
+    fn caption(image: &[u8]) -> Option<String> {
+        embedded_caption(&mut Cursor::new(image)).unwrap()
+    }
+
+    #[test]
+    fn png_text_chunks() {
+        let description = png_chunk(b"tEXt", b"Description\0A f\xfcr b");
+        assert_eq!(caption(&png(std::slice::from_ref(&description))).as_deref(),
+            Some("A für b"));
+
+        // the title is preferred over the description
+        let title = png_chunk(b"tEXt", b"Title\0The title");
+        assert_eq!(caption(&png(&[description, title])).as_deref(),
+            Some("The title"));
+    }
+
+    #[test]
+    fn png_international_text_chunks() {
+        let itxt = png_chunk(b"iTXt", "Description\0\0\0de\0Beschreibung\0Grüße"
+            .as_bytes());
+        assert_eq!(caption(&png(&[itxt])).as_deref(), Some("Grüße"));
+
+        let compressed = png_chunk(b"iTXt", b"Description\0\x01\0\0\0x\x9c");
+        assert_eq!(caption(&png(&[compressed])), None);
+    }
+
+    #[test]
+    fn png_text_after_image_data() {
+        let idat = png_chunk(b"IDAT", &[1, 2, 3]);
+        let title = png_chunk(b"tEXt", b"Title\0late");
+        assert_eq!(caption(&png(&[idat, title])), None);
+    }
+
+    #[test]
+    fn jpeg_comment() {
+        let app0 = (0xe0, b"JFIF\0\x01\x01".to_vec());
+        let comment = (0xfe, b" a comment ".to_vec());
+        assert_eq!(caption(&jpeg(&[app0, comment])).as_deref(),
+            Some("a comment"));
+    }
+
+    #[test]
+    fn jpeg_exif_description() {
+        for big_endian in [true, false] {
+            let image = jpeg(&[(0xfe, b"comment".to_vec()),
+                (0xe1, exif(big_endian, "from exif"))]);
+            assert_eq!(caption(&image).as_deref(), Some("from exif"));
+        }
+    }
+
+    #[test]
+    fn truncated_images() {
+        let images = [
+            png(&[png_chunk(b"tEXt", b"Title\0The title")]),
+            png(&[png_chunk(b"iTXt", b"Title\0\0\0\0\0The title")]),
+            jpeg(&[(0xfe, b"comment".to_vec())]),
+            jpeg(&[(0xe1, exif(true, "big endian")), (0xe1, exif(false, "little endian"))]),
+        ];
+
+        for image in images {
+            for len in 0..image.len() {
+                embedded_caption(&mut Cursor::new(&image[..len])).unwrap();
+            }
+        }
+
+        // the tiff structure is only read if the exif segment is complete,
+        // but its offsets may still point anywhere
+        let tiff = &exif(true, "big endian")[6..];
+        for len in 0..tiff.len() {
+            assert_eq!(exif_description(&tiff[..len]), None);
+        }
+    }
+}
@@ -0,0 +1,233 @@
+/*
+translations: a README.<lang>.md next to a README.md is the translation of
+the page into the language <lang> (e.g. README.de.md). The README.md is
+written in the default language of the site (`language` in the config), its
+translations are rendered into a tree prefixed with their language:
+
+    blog/README.md     -> blog/index.html
+    blog/README.de.md  -> de/blog/index.html
+
+The navigation and listing of a translated page link to the translations of
+the other pages, if they exist.
+*/
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::io;
+
+/// the language of a README file name: the default language (None) for
+/// README.md, <lang> for README.<lang>.md. Returns None for all other files.
+pub fn readme_language(name: &str) -> Option<Option<&str>> {
+    if name == "README.md" {
+        return Some(None);
+    }
+
+    let lang = name.strip_prefix("README.")?.strip_suffix(".md")?;
+    if is_language_tag(lang) {
+        Some(Some(lang))
+    } else {
+        None
+    }
+}
+
+/// whether the given file name is a README.md or one of its translations
+pub fn is_readme(name: &str) -> bool {
+    readme_language(name).is_some()
+}
+
+/// a simple language tag like `de`, `fra` or `pt-BR`
+fn is_language_tag(tag: &str) -> bool {
+    let (language, region) = match tag.split_once('-') {
+        Some((language, region)) => (language, Some(region)),
+        None => (tag, None),
+    };
+
+    (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_lowercase())
+        && region.is_none_or(|region| (2..=3).contains(&region.len())
+            && region.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// the languages each page of the site is available in
+#[derive(Debug)]
+pub struct Languages {
+    default: String,
+
+    /// the languages of the translations by page (the directory containing
+    /// the README.md relative to the input path)
+    translations: BTreeMap<PathBuf, BTreeSet<String>>,
+}
+
+impl Languages {
+
+    /// collect the translations in the given pathes
+    pub fn build(in_path: &Path, pathes: &[PathBuf], default: &str)
+        -> io::Result<Languages> {
+
+        let mut translations: BTreeMap<PathBuf, BTreeSet<String>> = BTreeMap::new();
+
+        for path in pathes {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            let lang = match readme_language(name) {
+                Some(Some(lang)) => lang,
+                _ => continue,
+            };
+
+            if lang == default {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!(
+                    "{}: `{}` is the default language of the site, its pages \
+                    are written to README.md", path.display(), lang)));
+            }
+
+            let page = path.parent().unwrap()
+                .strip_prefix(in_path)
+                .expect("could not strip the in_path prefix");
+            translations.entry(page.to_path_buf())
+                .or_default()
+                .insert(lang.to_string());
+        }
+
+        Ok(Languages {
+            default: default.to_string(),
+            translations,
+        })
+    }
+
+    /// the default language of the site
+    pub fn default(&self) -> &str {
+        &self.default
+    }
+
+    /// the languages the page is available in, starting with the default
+    /// language
+    pub fn of_page(&self, page: &Path) -> Vec<&str> {
+        let mut languages = vec![self.default.as_str()];
+        if let Some(translations) = self.translations.get(page) {
+            languages.extend(translations.iter().map(String::as_str));
+        }
+        languages
+    }
+
+    /// the absolute link to the page in the given language, falling back to
+    /// the default language if there is no such translation
+    pub fn link(&self, page: &Path, lang: &str) -> String {
+        let translated = lang != self.default
+            && self.translations.get(page)
+                .is_some_and(|translations| translations.contains(lang));
+
+        let link = if translated {
+            Path::new("/").join(lang).join(page)
+        } else {
+            Path::new("/").join(page)
+        };
+
+        link.to_str().unwrap().to_string()
+    }
+}
+
+/// make the relative links (`href` and `src` attributes) in a line of the
+/// translation of the page absolute. Translations are rendered into the tree
+/// prefixed with their language, the files next to their README are only
+/// copied to the unprefixed tree.
+pub fn absolute_links(line: &str, page: &Path) -> String {
+    let base = match page.to_str().unwrap() {
+        "" => "/".to_string(),
+        page => format!("/{}/", page),
+    };
+
+    let mut output = String::new();
+    let mut rest = line;
+
+    while let Some((start, attribute)) = ["href=\"", "href='", "src=\"", "src='"]
+        .iter()
+        .filter_map(|attribute| Some((rest.find(attribute)?, attribute)))
+        .min() {
+
+        let value_start = start + attribute.len();
+        output.push_str(&rest[..value_start]);
+        rest = &rest[value_start..];
+
+        let quote = attribute.chars().last().unwrap();
+        let value = &rest[..rest.find(quote).unwrap_or(rest.len())];
+        if is_relative(value) {
+            output.push_str(&base);
+        }
+        output.push_str(value);
+        rest = &rest[value.len()..];
+    }
+
+    output.push_str(rest);
+    output
+}
+
+/// whether the link is relative to the page, not absolute, an anchor on the
+/// page itself or a link to another site (`https:`, `mailto:`, ...)
+fn is_relative(link: &str) -> bool {
+    !link.is_empty()
+        && !link.starts_with(['/', '#', '?'])
+        && !link.split('/').next().unwrap().contains(':')
+}
+
+/// write the <link rel="alternate"> elements pointing to all translations of
+/// the page, nothing if the page isn't translated
+pub fn write_alternates(file: &mut dyn io::Write, languages: &Languages,
+    page: &Path, base_url: &str) -> io::Result<()> {
+
+    let page_languages = languages.of_page(page);
+    if page_languages.len() < 2 {
+        return Ok(());
+    }
+
+    for lang in page_languages {
+        file.write_all(format!(r#"
+  <link rel="alternate" hreflang="{}" href="{}{}">"#,
+            lang, base_url.trim_end_matches('/'), languages.link(page, lang))
+            .as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// write the links to the other translations of the page as items of the
+/// navigation, nothing if the page isn't translated
+pub fn write_switcher(file: &mut dyn io::Write, languages: &Languages,
+    page: &Path, current: &str) -> io::Result<()> {
+
+    let page_languages = languages.of_page(page);
+    if page_languages.len() < 2 {
+        return Ok(());
+    }
+
+    for lang in page_languages {
+        if lang == current {
+            file.write_all(format!(r#"
+        <li lang="{}">{}</li>"#, lang, lang).as_bytes())?;
+        } else {
+            file.write_all(format!(r#"
+        <li><a href="{}" hreflang="{}" lang="{}">{}</a></li>"#,
+                languages.link(page, lang), lang, lang, lang).as_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_links_become_absolute() {
+        assert_eq!(absolute_links(r#"<img src="img.png"> <a href='sub/'>x</a>"#,
+            Path::new("blog")),
+            r#"<img src="/blog/img.png"> <a href='/blog/sub/'>x</a>"#);
+        assert_eq!(absolute_links(r#"<a href="a.pdf">"#, Path::new("")),
+            r#"<a href="/a.pdf">"#);
+    }
+
+    #[test]
+    fn other_links_are_kept() {
+        let line = r##"<a href="/x"><a href="#top"><a href="https://a.b/c"><a href="mailto:a@b">"##;
+        assert_eq!(absolute_links(line, Path::new("blog")), line);
+    }
+}
@@ -0,0 +1,154 @@
+/*
+the site wide link graph: which page links to which other pages. Used by the
+`:::backlinks` directive to list all pages linking to the current one.
+*/
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Component, Path, PathBuf};
+use std::io::{self, Write};
+
+use crate::directive::{Context, Directive};
+use crate::tree::SiteTree;
+
+/// maps every page (the directory containing the README.md relative to the
+/// input path) to the set of pages linking to it
+#[derive(Debug, Default)]
+pub struct LinkGraph {
+    backlinks: BTreeMap<PathBuf, BTreeSet<PathBuf>>,
+}
+
+impl LinkGraph {
+
+    /// collect the links between all README.md files of the site
+    pub fn build(tree: &SiteTree) -> LinkGraph {
+        let mut graph = LinkGraph::default();
+
+        for (path, readme) in tree.readmes() {
+            if !path.ends_with("README.md") {
+                continue
+            }
+
+            let source = path.parent()
+                .expect("could not extract the page of the README.md");
+
+            for link in extract_links(readme) {
+                if let Some(target) = resolve_link(source, link) {
+                    // links from a page to itself aren't interesting
+                    if target == source {
+                        continue
+                    }
+                    graph.backlinks.entry(target)
+                        .or_default()
+                        .insert(source.to_path_buf());
+                }
+            }
+        }
+
+        graph
+    }
+
+    /// all pages linking to the given page, sorted by their path
+    pub fn backlinks(&self, page: &Path) -> impl Iterator<Item = &PathBuf> {
+        self.backlinks.get(page).into_iter().flatten()
+    }
+}
+
+/// Write a list of all pages linking to the current page to the file
+pub fn write_backlinks(file: &mut dyn Write, ctx: &Context, _directive: &Directive)
+    -> io::Result<()> {
+    write_backlink_list(file, ctx.links, ctx.raw_path)
+}
+
+/// Write a list of all pages linking to the page at `raw_path` to the file
+fn write_backlink_list(file: &mut dyn Write, links: &LinkGraph, raw_path: &Path)
+    -> io::Result<()> {
+
+    for source in links.backlinks(raw_path) {
+        let link = Path::new("/").join(source);
+        file.write_all(format!("<a href=\"{}\">{}</a>\n",
+            link.display(), link.display()).as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Write a separate backlinks section listing all pages linking to the page at
+/// `raw_path`, if there are any
+pub fn write_backlinks_section(file: &mut dyn Write, links: &LinkGraph,
+    raw_path: &Path) -> io::Result<()> {
+
+    if links.backlinks(raw_path).next().is_none() {
+        return Ok(());
+    }
+
+    file.write_all(r#"</pre>
+            <hr>
+            <h2>Backlinks</h2>
+            <pre>"#.as_bytes())?;
+    write_backlink_list(file, links, raw_path)?;
+
+    Ok(())
+}
+
+/// extract the targets of all html (`href="..."`) and markdown (`](...)`)
+/// links in the given text
+fn extract_links(text: &str) -> Vec<&str> {
+    let mut links = Vec::new();
+
+    for (start, end) in [("href=\"", '"'), ("href='", '\''), ("](", ')')] {
+        let mut rest = text;
+        while let Some(i) = rest.find(start) {
+            rest = &rest[i + start.len()..];
+            if let Some(j) = rest.find(end) {
+                links.push(&rest[..j]);
+                rest = &rest[j..];
+            }
+        }
+    }
+
+    links
+}
+
+/// resolve the link found on the page `source` to the page it points to.
+/// Returns None for links leaving the site (other schemes, protocol relative
+/// links) or pointing at the page itself (anchors).
+fn resolve_link(source: &Path, link: &str) -> Option<PathBuf> {
+    let link = link.trim();
+
+    if link.contains("://")
+        || link.starts_with("//")
+        || link.starts_with("mailto:")
+        || link.starts_with("tel:")
+        || link.starts_with('#')
+        || link.is_empty()
+        {
+        return None;
+    }
+
+    // drop the fragment and query parts
+    let link = link.split(['#', '?']).next().unwrap();
+
+    let mut target = match link.strip_prefix('/') {
+        Some(absolute) => PathBuf::from(absolute),
+        None => source.join(link),
+    };
+
+    // links to the page itself instead of its directory
+    if target.ends_with("index.html") || target.ends_with("README.md") {
+        target.pop();
+    }
+
+    // normalize `.` and `..` without touching the filesystem
+    let mut normalized = PathBuf::new();
+    for component in target.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            },
+            Component::Normal(c) => normalized.push(c),
+            _ => {},
+        }
+    }
+
+    Some(normalized)
+}
@@ -0,0 +1,116 @@
+/*
+the build lock: only one build may write to an output path at a time, so that
+a watch script and a manual build can't interleave their writes. The lock is
+a file next to the output path (`.out.lock` for `out`) containing the pid of
+the build holding it. The file itself is locked by the operating system, so
+the lock is released when a build crashes and the file it leaves behind is
+taken over by the next build.
+*/
+
+use std::path::{Path, PathBuf};
+use std::io::{self, Read, Seek, Write};
+use std::fs::{self, File, OpenOptions, TryLockError};
+
+pub struct BuildLock {
+    path: PathBuf,
+    file: File,
+}
+
+impl BuildLock {
+
+    /// lock the given output path. Fails if another build holds the lock,
+    /// unless `wait` is given, then it waits for the other build to finish.
+    pub fn acquire(output_path: &Path, wait: bool) -> io::Result<BuildLock> {
+        let path = crate::sibling_path(output_path, ".lock")?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        loop {
+            let mut file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)?;
+
+            match file.try_lock() {
+                Ok(()) => {},
+                Err(TryLockError::WouldBlock) => {
+                    let holder = read_holder(&mut file);
+                    if !wait {
+                        return Err(io::Error::new(io::ErrorKind::ResourceBusy,
+                            format!("another build ({}) is writing to {}, wait \
+                            for it to finish or use --wait",
+                            holder, output_path.display())));
+                    }
+
+                    println!("Waiting for another build ({}) writing to {}",
+                        holder, output_path.display());
+                    file.lock()?;
+                },
+                Err(TryLockError::Error(e)) => return Err(e),
+            }
+
+            // the build holding the lock removes the file before releasing
+            // it, a lock on a removed file is worthless
+            if !is_same_file(&file, &path)? {
+                continue
+            }
+
+            // the file of a build that crashed
+            let holder = read_holder(&mut file);
+            if holder != "unknown pid" {
+                println!("Taking over the lock of a crashed build ({})",
+                    holder);
+            }
+
+            file.set_len(0)?;
+            file.rewind()?;
+            write!(file, "{}", std::process::id())?;
+
+            return Ok(BuildLock { path, file });
+        }
+    }
+}
+
+impl Drop for BuildLock {
+
+    /// remove the lock file, the lock itself is released when the file is
+    /// closed right after
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+        let _ = self.file.unlock();
+    }
+}
+
+/// the pid of the build written to the lock file
+fn read_holder(file: &mut File) -> String {
+    let mut pid = String::new();
+    let _ = file.rewind().and_then(|_| file.read_to_string(&mut pid));
+
+    match pid.trim() {
+        "" => "unknown pid".to_string(),
+        pid => format!("pid {}", pid),
+    }
+}
+
+/// whether the open file is still the one at the given path
+#[cfg(unix)]
+fn is_same_file(file: &File, path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let opened = file.metadata()?;
+    match fs::metadata(path) {
+        Ok(current) => Ok(opened.dev() == current.dev()
+            && opened.ino() == current.ino()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(not(unix))]
+fn is_same_file(_file: &File, path: &Path) -> io::Result<bool> {
+    Ok(path.is_file())
+}
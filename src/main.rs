@@ -5,12 +5,51 @@ even when running offline
 #[doc(inline)]
 pub use std;
 
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
-use std::io::{self, Read, Write, BufRead, BufReader};
+use std::io::{self, Write, BufRead, BufReader};
 use std::fs::{self, File};
 use std::time;
 use structopt::StructOpt;
 
+mod assets;
+mod cache;
+mod config;
+mod css;
+mod directive;
+mod frontmatter;
+mod gallery;
+mod i18n;
+mod links;
+mod lock;
+mod manifest;
+mod pool;
+mod publish;
+mod settings;
+mod table;
+mod template;
+mod theme;
+mod timings;
+mod tree;
+mod variables;
+
+use assets::AssetMode;
+use cache::{Cache, Dependencies};
+use config::Config;
+use css::{Stylesheet, Stylesheets};
+use frontmatter::FrontMatter;
+use i18n::Languages;
+use links::LinkGraph;
+use lock::BuildLock;
+use manifest::Manifest;
+use publish::Staging;
+use settings::{Layout, Listing, Settings, Sort};
+use template::Template;
+use theme::Theme;
+use timings::Timings;
+use tree::SiteTree;
+use variables::Variables;
+
 #[derive(Debug, StructOpt)]
 #[structopt(name = "vokobe", about = "A static site generator")]
 struct Opt {
@@ -18,62 +57,240 @@ struct Opt {
     #[structopt(parse(from_os_str))]
     input_path: PathBuf,
 
-    /// Output path
+    /// Output path (overrides `output.path` from the config)
     #[structopt(parse(from_os_str))]
-    output_path: PathBuf,
+    output_path: Option<PathBuf>,
+
+    /// Site name (e.g. emile.space) (overrides `name` from the config)
+    site_name: Option<String>,
+
+    /// Config file [default: <input-path>/vokobe.toml]
+    #[structopt(short, long, parse(from_os_str))]
+    config: Option<PathBuf>,
+
+    /// Url the site is hosted at (overrides `base_url` from the config)
+    #[structopt(long)]
+    base_url: Option<String>,
+
+    /// Author of the site (overrides `author` from the config)
+    #[structopt(long)]
+    author: Option<String>,
+
+    /// Language of the site (overrides `language` from the config)
+    #[structopt(long)]
+    language: Option<String>,
+
+    /// Theme directory (overrides `theme` from the config)
+    #[structopt(long, parse(from_os_str))]
+    theme: Option<PathBuf>,
+
+    /// Amount of pages rendered and files copied in parallel [default: the
+    /// available parallelism]
+    #[structopt(short, long)]
+    jobs: Option<usize>,
+
+    /// Keep the files of earlier builds that lost their source in the output
+    #[structopt(long)]
+    no_prune: bool,
 
-    /// Site name (e.g. emile.space)
-    site_name: String,
+    /// Ignore the build cache, rerender all pages and recopy all files
+    #[structopt(long)]
+    rebuild: bool,
+
+    /// Inline the stylesheet into every page instead of linking it
+    #[structopt(long)]
+    inline_style: bool,
+
+    /// How files are written to the output: copy, hardlink or reflink
+    /// (overrides `output.assets` from the config)
+    #[structopt(long)]
+    assets: Option<String>,
+
+    /// Show the time of the build in the footer ($SOURCE_DATE_EPOCH if set)
+    #[structopt(long)]
+    build_time: bool,
+
+    /// Wait for another build writing to the output path to finish instead of
+    /// failing
+    #[structopt(long)]
+    wait: bool,
+
+    /// Print which pages would be rendered and which files copied or removed,
+    /// without writing anything
+    #[structopt(long)]
+    dry_run: bool,
+
+    /// Print how long the build and each page took, and write a json report
+    /// next to the output path (.out.timings.json for out)
+    #[structopt(long)]
+    timings: bool,
 
     /// Activate sending analytics to stats.emile.space
     // -a and --analytics will be generated
     // analytics are sent to stats.emile.space
     #[structopt(short, long)]
     analytics: bool,
+
+    /// Append a list of all pages linking to a page to each page
+    #[structopt(short, long)]
+    backlinks: bool,
 }
 
 fn main() -> std::io::Result<()> {
 
     let opt = Opt::from_args();
 
-    let in_path = opt.input_path;
-    let output_path = opt.output_path;
+    let config = load_config(&opt)?;
+
+    let output_path = match &config.output.path {
+        Some(path) => path.clone(),
+        None => return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            "no output path given, neither on the command line nor as \
+            `output.path` in the config")),
+    };
+
+    let site_name = match &config.name {
+        Some(name) => name.clone(),
+        None => return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            "no site name given, neither on the command line nor as `name` \
+            in the config")),
+    };
+
+    let mut timings = Timings::new();
+
+    // a dry run only prints what a build would do to the output path
+    if opt.dry_run {
+        build(&opt, &config, &site_name, &output_path, &output_path,
+            &mut timings)?;
+        return Ok(());
+    }
+
+    // only one build may write to the output path at a time, the lock is
+    // released when it goes out of scope
+    let _lock = BuildLock::acquire(&output_path, opt.wait)?;
+    timings.phase("lock");
+
+    // the build is written to a staging directory, which only replaces the
+    // output path if the build succeeded
+    let staging = Staging::new(&output_path)?;
+    timings.phase("staging");
+
+    let result = build(&opt, &config, &site_name, staging.path(),
+        &output_path, &mut timings);
+    let built = match result {
+        Ok(built) => built,
+        Err(e) => {
+            staging.discard()?;
+            return Err(e);
+        },
+    };
+    staging.publish()?;
+
+    // the cache and manifest describe the published output, they are only
+    // written once it is in place
+    if let Some((cache, manifest)) = built {
+        cache.save()?;
+        manifest.save()?;
+    }
+    timings.phase("publish");
+
+    if opt.timings {
+        timings.print_summary();
 
-    // read the style
-    let style_path = Path::new(&in_path).join("style.css");
-    let mut style_file = File::open(style_path)
-        .expect("could not open style file");
-    let mut style = String::new();
-    style_file.read_to_string(&mut style)
-        .expect("could not read style file to string");
+        let report_path = sibling_path(&output_path, ".timings.json")?;
+        timings.save(&report_path)?;
+        println!("Wrote the timings to {}", report_path.display());
+    }
+
+    Ok(())
+}
 
-    // read all dirs in the input path
-    let pathes = recursive_read_dir(&in_path, false)?;
+/// Build the site into `output_path`, the staging directory published to
+/// `published_path`. Returns the cache and manifest of the build, nothing
+/// for a dry run.
+fn build(opt: &Opt, config: &Config, site_name: &str, output_path: &Path,
+    published_path: &Path, timings: &mut Timings)
+    -> io::Result<Option<(Cache, Manifest)>> {
+
+    let in_path = &opt.input_path;
+
+    // the theme providing the files missing in the input path
+    let theme = Theme::new(in_path, config.theme.as_deref())?;
+
+    // the stylesheets of the pages, bundled on first use
+    let mut stylesheets = Stylesheets::new(&theme, output_path,
+        config.output.inline_style);
+
+    // read the template composing the pages
+    let template = Template::load(&theme)?;
+
+    // the templates selected in the directory settings by their name
+    let mut templates: BTreeMap<String, Template> = BTreeMap::new();
+
+    // the build cache of the last build, deciding which pages have to be
+    // rendered again. Any change of the config invalidates it.
+    let fingerprint = content_hash(format!("{} {:?}",
+        env!("CARGO_PKG_VERSION"), config).as_bytes());
+    let cache_path = sibling_path(published_path, cache::CACHE_SUFFIX)?;
+    let mut cache = match opt.rebuild {
+        true => Cache::empty(output_path, &cache_path, &fingerprint),
+        false => Cache::load(output_path, &cache_path, &fingerprint)?,
+    };
+    timings.phase("load");
+
+    // a fixed time the build is reproducible with, no modification time is
+    // later than it and it is the time of the build shown in the footer
+    let source_date = source_date_epoch()?;
+    let build_time = match config.output.build_time {
+        true => Some(source_date.unwrap_or_else(time::SystemTime::now)),
+        false => None,
+    };
+
+    // scan the input path once, everything is rendered from this snapshot
+    let tree = SiteTree::scan(in_path, config, source_date)?;
+    let pathes = tree.pathes(Path::new(""), false);
 
     println!("Got {} files", pathes.len());
+    timings.phase("scan");
 
-    for path in pathes {
-        let stripped_path = path.strip_prefix(&in_path)
-            .expect(format!(
-                "could not strip the in_path prefix: {:?}", in_path).as_str());
+    // collect the links between all pages, so that each page can list the
+    // pages linking to it
+    let links = LinkGraph::build(&tree);
 
-        // copy images and other files to the output folder
-        if path.is_file() {
+    // collect the translations of all pages, so that each page can link to
+    // its translations
+    let languages = Languages::build(in_path, &pathes, config.language())?;
 
-            // define the source and destination
-            let src = Path::new(&in_path).join(stripped_path);
-            let dst = Path::new(&output_path).join(stripped_path);
+    // the pages to render and the files to copy, in the order of the pathes
+    let mut pages = Vec::new();
+    let mut assets = Vec::new();
 
-            // define the destination folder (the dst path without the file) and create it
-            let mut dst_folder = dst.clone();
-            dst_folder.pop(); // remove the file itself from the path
-            fs::create_dir_all(dst_folder)?;
+    // the README each index.html is rendered from, a translation may end up
+    // at the same path as a page in a directory named like its language
+    let mut page_sources: BTreeMap<PathBuf, PathBuf> = BTreeMap::new();
 
-            // copy the file to the destination
-            std::fs::copy(src, dst.as_path())?;
+    for path in pathes {
+        let stripped_path = path.strip_prefix(in_path)
+            .unwrap_or_else(|_| panic!(
+                "could not strip the in_path prefix: {:?}", in_path));
+
+        // copy images and other files to the output folder, except for the
+        // template and config, which aren't part of the site
+        let entry = tree.entry(stripped_path).unwrap();
+        if entry.is_file
+            && stripped_path != Path::new(template::TEMPLATE_FILE)
+            && stripped_path != Path::new(config::CONFIG_FILE)
+            && !stripped_path.starts_with(template::TEMPLATES_DIR)
+            && !settings::is_settings_file(entry)
+            && !theme.contains(&path)
+            {
+            assets.push((path.clone(), output_path.join(stripped_path)));
         }
 
-        if stripped_path.ends_with("README.md") {
+        // the README.md and its translations (README.<lang>.md) are rendered
+        // to pages, the translations into a tree prefixed with their language
+        let readme_name = stripped_path.file_name().unwrap().to_str().unwrap();
+        if let Some(translation) = i18n::readme_language(readme_name) {
 
             // define the "raw" path (no infile prefix, no file)
             let mut ancestors = stripped_path.ancestors();
@@ -82,88 +299,468 @@ fn main() -> std::io::Result<()> {
             let raw_path = ancestors.next()
                 .expect("could not extract next ancestor");
 
-            // out + rawpath
-            let index_path = output_path.join(raw_path);
+            let lang = translation.unwrap_or(languages.default());
+
+            // out + (lang) + rawpath
+            let index_path = match translation {
+                Some(lang) => output_path.join(lang).join(raw_path),
+                None => output_path.join(raw_path),
+            };
 
             // (out + rawpath) + "index.html"
             let index_file = index_path.join("index.html");
 
-            // - create the dir for the index.html as well as the index.html
-            // itself
-            fs::create_dir_all(index_path)?;
-            let mut file = File::create(&index_file)?;
-
-            // this is the main block calling all other smaller functions. The
-            // whole output is compsed here
-            write_header(&mut file, &opt.site_name, &style)?;
-            write_body_start(&mut file, &opt.site_name)?;
-            write_nav(&mut file, in_path.as_path(), raw_path, opt.analytics)?;
-            write_same_level(&mut file, in_path.as_path(), raw_path)?;
-            write_readme_content(&mut file, in_path.as_path(), raw_path)?;
-            write_footer(&mut file)?;
-
-            file.write_all("".as_bytes())?;
+            if let Some(other) = page_sources.insert(index_file.clone(), path.clone()) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!(
+                    "{} and {} are both rendered to {}", other.display(),
+                    path.display(), published_path.join(index_file
+                        .strip_prefix(output_path).unwrap()).display())));
+            }
+
+            let settings = Settings::for_dir(&tree, raw_path);
+
+            // load the template selected in the settings on first use
+            if let Some(name) = &settings.template {
+                if !templates.contains_key(name) {
+                    templates.insert(name.clone(),
+                        Template::load_named(&theme, name)?);
+                }
+            }
+            let template = match &settings.template {
+                Some(name) => &templates[name],
+                None => &template,
+            };
+
+            let stylesheet = stylesheets.for_page(&tree, raw_path)?;
+
+            // everything the page is made of, the page is only rendered if
+            // any of it changed since the last build
+            let dependencies = Dependencies::default();
+            dependencies.page(&tree, raw_path);
+            dependencies.file(&path);
+            dependencies.value("updated",
+                &format_date(entry.modified));
+            dependencies.value("modified", &format!("{:?}", tree.modified()));
+
+            // the listing may be sorted by the dates of the entries, which
+            // change without changing the directory
+            let (dirs, files) = listed_entries(&tree, raw_path, &settings);
+            dependencies.value("listing", &format!("{:?}", dirs.iter().chain(&files)
+                .map(|entry| &entry.name).collect::<Vec<_>>()));
+            if let Some(template_path) = &template.path {
+                dependencies.file(template_path);
+            }
+            dependencies.value("stylesheet", &stylesheet.html);
+            dependencies.value("backlinks",
+                &format!("{:?}", links.backlinks(raw_path).collect::<Vec<_>>()));
+            dependencies.value("languages", &format!("{:?}", languages));
+            if let Some(build_time) = build_time {
+                dependencies.value("build_time", &format!("{:?}", build_time));
+            }
+
+            pages.push(Page {
+                path: path.clone(),
+                raw_path: raw_path.to_path_buf(),
+                readme_name: readme_name.to_string(),
+                lang: lang.to_string(),
+                index_file,
+                settings,
+                stylesheet,
+                dependencies,
+            });
         }
+    }
+
+    // copy the assets of the theme not overridden by the input path
+    for asset in theme.assets()? {
+        assets.push((theme.asset_path(&asset).unwrap(), output_path.join(&asset)));
+    }
+
+    let jobs = opt.jobs.unwrap_or_else(pool::default_jobs);
+
+    // everything this build writes to the output path
+    let manifest_path = sibling_path(published_path, manifest::MANIFEST_SUFFIX)?;
+    let mut manifest = Manifest::new(output_path, &manifest_path);
+    for (_, dst) in &assets {
+        manifest.add(dst);
+    }
+    for page in &pages {
+        manifest.add(&page.index_file);
+        if let Some(style_path) = &page.stylesheet.path {
+            manifest.add(style_path);
+        }
+    }
+    timings.phase("prepare");
+
+    if opt.dry_run {
+        let last_manifest = Manifest::load(output_path, &manifest_path)?;
+        let stale = match opt.no_prune {
+            true => Vec::new(),
+            false => manifest.stale(&last_manifest).collect(),
+        };
+        print_plan(jobs, &tree, &cache, &assets, &pages, &stylesheets, &stale)?;
+        return Ok(None);
+    }
+
+    // copy the files and render the pages in parallel. The results are in
+    // the order of the pathes, so the first error is always the same one.
+    let copied = pool::run(jobs, &assets, |(src, dst)| cache.copy_asset(src, dst, config.output.assets));
+    for ((_, dst), hash) in assets.iter().zip(copied) {
+        cache.record_asset(dst, hash?);
+    }
+    stylesheets.write()?;
+    timings.phase("copy");
+
+    let site = Site {
+        tree: &tree,
+        site_name,
+        config,
+        links: &links,
+        languages: &languages,
+        build_time,
+    };
+
+    let render = |page: &Page| {
+        if cache.is_fresh(&tree, &page.index_file, &page.dependencies)? {
+            return Ok(None);
+        }
+
+        let template = match &page.settings.template {
+            Some(name) => &templates[name],
+            None => &template,
+        };
+        write_page(&site, page, template)?;
+
+        cache.hashes(&page.dependencies).map(Some)
+    };
+    let results = pool::run(jobs, &pages, |page| {
+        let start = time::Instant::now();
+        let result = render(page);
+        (result, start.elapsed())
+    });
+
+    // the amount of rendered and unchanged pages
+    let (mut rendered, mut unchanged) = (0, 0);
+
+    for (page, (result, elapsed)) in pages.iter().zip(results) {
+        let source = page.path.strip_prefix(in_path).unwrap();
+        match result? {
+            Some(hashes) => {
+                cache.record_page(&page.index_file, hashes);
+                timings.page(source, elapsed, true);
+                rendered += 1;
+            },
+            None => {
+                cache.keep_page(&page.index_file);
+                timings.page(source, elapsed, false);
+                unchanged += 1;
+            },
+        }
+    }
+    timings.phase("render");
+
+    // remove the files of the last build that weren't written again, their
+    // source is gone
+    let last_manifest = Manifest::load(output_path, &manifest_path)?;
+    if opt.no_prune {
+        manifest.keep_stale(&last_manifest);
+    } else {
+        for path in manifest.prune(&last_manifest)? {
+            let rel_path = path.strip_prefix(output_path).unwrap();
+            println!("Removed {}", published_path.join(rel_path).display());
+        }
+    }
+    timings.phase("prune");
+
+    println!("Rendered {} pages, {} unchanged", rendered, unchanged);
 
+    Ok(Some((cache, manifest)))
+}
+
+/// Print what a build would do to the output path without writing anything:
+/// the files it copies or skips, the pages it renders or skips and the files
+/// of the last build it removes
+fn print_plan(jobs: usize, tree: &SiteTree, cache: &Cache,
+    assets: &[(PathBuf, PathBuf)], pages: &[Page], stylesheets: &Stylesheets,
+    stale: &[&PathBuf]) -> io::Result<()> {
+
+    let rel = |path: &Path| cache.key(path).display().to_string();
+
+    let (mut copied, mut skipped) = (0, 0);
+    let checked = pool::run(jobs, assets, |(src, dst)| cache.check_asset(src, dst));
+    for ((_, dst), checked) in assets.iter().zip(checked) {
+        match checked?.1 {
+            true => {
+                println!("skip    {}", rel(dst));
+                skipped += 1;
+            },
+            false => {
+                println!("copy    {}", rel(dst));
+                copied += 1;
+            },
+        }
+    }
+    for path in stylesheets.unwritten().keys() {
+        println!("write   {}", rel(path));
+    }
+
+    let (mut rendered, mut unchanged) = (0, 0);
+    let fresh = pool::run(jobs, pages, |page| {
+        cache.is_fresh(tree, &page.index_file, &page.dependencies)
+    });
+    for (page, fresh) in pages.iter().zip(fresh) {
+        match fresh? {
+            true => {
+                println!("skip    {}", rel(&page.index_file));
+                unchanged += 1;
+            },
+            false => {
+                println!("render  {}", rel(&page.index_file));
+                rendered += 1;
+            },
+        }
     }
 
+    for rel_path in stale {
+        println!("remove  {}", rel_path.display());
+    }
+
+    println!("Would render {} pages ({} unchanged), copy {} files ({} \
+        unchanged) and remove {} files", rendered, unchanged, copied, skipped,
+        stale.len());
+
     Ok(())
 }
 
-/// Write the html header including the style file
-/// TODO: Don't add the style file into each compiled html output, as the
-/// style can be included allowing the user to cache the style file in their
-/// browser.
-fn write_header(file: &mut File, site_name: &String, style: &String) -> std::io::Result<()>{
-
-    // write the header including the style file
-    file.write_all(format!(r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-  <meta charset="UTF-8">
-  <meta http-equiv="X-UA-Compatible" content="IE=edge">
-  <meta name="viewport" content="width=device-width, initial-scale=1.0">
-  <title>{}</title>
-
-  <style>
-  {}
-  </style>
-</head>
-    "#, site_name, style).as_bytes())?;
+/// the site wide state the pages are rendered with
+struct Site<'a> {
+    tree: &'a SiteTree,
+    site_name: &'a str,
+    config: &'a Config,
+    links: &'a LinkGraph,
+    languages: &'a Languages,
+
+    /// the time of the build shown in the footer, if any
+    build_time: Option<time::SystemTime>,
+}
+
+/// a page to render: a README.md or one of its translations
+struct Page {
+    /// the path of the README.md
+    path: PathBuf,
+
+    /// the directory of the README.md relative to the input path
+    raw_path: PathBuf,
+    readme_name: String,
+    lang: String,
+
+    /// the path of the index.html in the output path
+    index_file: PathBuf,
+
+    settings: Settings,
+    stylesheet: Stylesheet,
+    dependencies: Dependencies,
+}
+
+/// Render the page with the given template and write it to its index.html
+fn write_page(site: &Site, page: &Page, template: &Template)
+    -> std::io::Result<()> {
+
+    let tree = site.tree;
+    let config = site.config;
+    let raw_path = page.raw_path.as_path();
+    let lang = page.lang.as_str();
+
+    // this is the main block calling all other smaller functions. The parts
+    // of the page are rendered here and composed by the template. The content
+    // comes first, as its front matter may select the layout deciding which
+    // other parts are shown.
+    let mut content = Vec::new();
+    let front_matter = write_readme_content(&mut content, tree, raw_path,
+        &page.readme_name, site.links, config, &page.dependencies)?;
+    if config.features.backlinks {
+        links::write_backlinks_section(&mut content, site.links, raw_path)?;
+    }
+
+    let layout = match front_matter.get("layout") {
+        Some(name) => Layout::parse(name).map_err(|e| io::Error::new(
+            e.kind(), format!("{}: {}", page.path.display(), e)))?,
+        None => page.settings.layout,
+    };
+    let shows = |part| layout.parts().contains(&part);
+
+    let mut header = Vec::new();
+    if shows("header") {
+        write_header(&mut header, site.site_name,
+            &site.languages.link(Path::new(""), lang))?;
+    }
+
+    let mut nav = Vec::new();
+    if shows("nav") {
+        write_nav(&mut nav, tree, raw_path, config, site.languages, lang)?;
+    }
+
+    let mut listing = Vec::new();
+    if shows("listing") {
+        write_same_level(&mut listing, tree, raw_path, &page.settings,
+            site.languages, lang)?;
+    }
+
+    let mut footer = Vec::new();
+    if shows("footer") {
+        write_footer(&mut footer, config, site.build_time)?;
+    }
+
+    let mut alternates = Vec::new();
+    i18n::write_alternates(&mut alternates, site.languages, raw_path,
+        config.base_url.as_deref().unwrap_or_default())?;
+
+    let title = front_matter.get("title").unwrap_or(site.site_name);
+
+    let variables = BTreeMap::from([
+        ("site_name", site.site_name.to_string()),
+        ("title", title.to_string()),
+        ("base_url", config.base_url.clone().unwrap_or_default()),
+        ("author", config.author.clone().unwrap_or_default()),
+        ("lang", lang.to_string()),
+        ("alternates", String::from_utf8_lossy(&alternates).into_owned()),
+        ("style", page.stylesheet.style.clone()),
+        ("stylesheet", page.stylesheet.html.clone()),
+        ("header", String::from_utf8_lossy(&header).into_owned()),
+        ("nav", String::from_utf8_lossy(&nav).into_owned()),
+        ("listing", String::from_utf8_lossy(&listing).into_owned()),
+        ("content", String::from_utf8_lossy(&content).into_owned()),
+        ("footer", String::from_utf8_lossy(&footer).into_owned()),
+        ("version", env!("CARGO_PKG_VERSION").to_string()),
+    ]);
+
+    // - create the dir for the index.html as well as the index.html itself
+    fs::create_dir_all(page.index_file.parent().unwrap())?;
+    let mut file = create_file(&page.index_file)?;
+    file.write_all(template.render(&variables).as_bytes())?;
 
     Ok(())
 }
 
-/// write the start of the html body tag and the header linking back to the
-/// site itself.
-fn write_body_start(file: &mut File, site_name: &String) -> std::io::Result<()>{
+/// Create the file at `path`, see `remove_existing`
+fn create_file(path: &Path) -> io::Result<File> {
+    remove_existing(path)?;
+    File::create(path)
+}
+
+/// Remove the file at `path` if it exists, before it is written again. Files
+/// in the output are replaced instead of being written to, as they may be
+/// hard links to the published output or to the input.
+fn remove_existing(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// the path of the hidden file next to the output path with the given suffix,
+/// e.g. `.out.lock` for `out`. Everything vokobe keeps about an output path is
+/// stored next to it, so that it isn't published with the site.
+fn sibling_path(output_path: &Path, suffix: &str) -> io::Result<PathBuf> {
+    let name = output_path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!(
+            "the output path {:?} has no name", output_path)))?;
+
+    let mut sibling_name = std::ffi::OsString::from(".");
+    sibling_name.push(name);
+    sibling_name.push(suffix);
+
+    Ok(output_path.with_file_name(sibling_name))
+}
+
+/// Load the config file and override its values with the ones given on the
+/// command line
+fn load_config(opt: &Opt) -> io::Result<Config> {
+    let config_path = match &opt.config {
+        Some(path) => path.clone(),
+        None => opt.input_path.join(config::CONFIG_FILE),
+    };
+
+    // an explicitly given config file has to exist
+    let mut config = Config::load(&config_path, &opt.input_path,
+        opt.config.is_some())?;
+
+    // relative output and theme paths in the config are relative to the
+    // input path
+    if let Some(path) = &config.output.path {
+        config.output.path = Some(opt.input_path.join(path));
+    }
+    if let Some(path) = &config.theme {
+        config.theme = Some(opt.input_path.join(path));
+    }
+
+    if let Some(path) = &opt.output_path {
+        config.output.path = Some(path.clone());
+    }
+    if let Some(path) = &opt.theme {
+        config.theme = Some(path.clone());
+    }
+    if let Some(name) = &opt.site_name {
+        config.name = Some(name.clone());
+    }
+    if let Some(base_url) = &opt.base_url {
+        config.base_url = Some(base_url.clone());
+    }
+    if let Some(author) = &opt.author {
+        config.author = Some(author.clone());
+    }
+    if let Some(language) = &opt.language {
+        config.language = Some(language.clone());
+    }
+    if opt.analytics {
+        config.features.analytics = true;
+    }
+    if opt.backlinks {
+        config.features.backlinks = true;
+    }
+    if opt.inline_style {
+        config.output.inline_style = true;
+    }
+    if let Some(mode) = &opt.assets {
+        config.output.assets = AssetMode::parse(mode)?;
+    }
+    if opt.build_time {
+        config.output.build_time = true;
+    }
+
+    Ok(config)
+}
+
+/// Write the header linking to the root of the site (`root_link`) to the
+/// given file
+fn write_header(file: &mut dyn Write, site_name: &str, root_link: &str)
+    -> std::io::Result<()> {
     file.write_all(format!(r#"
-<body>
   <header>
-    <a href="/">{}</a>
-  </header>"#, site_name).as_bytes())?;
+    <a href="{}">{}</a>
+  </header>"#, root_link, site_name).as_bytes())?;
 
     Ok(())
 }
 
 /// Write the navigation section to the given file
-fn write_nav(file: &mut File, in_path: &Path, raw_path: &Path, analytics: bool)
-    -> std::io::Result<()> {
+fn write_nav(file: &mut dyn Write, tree: &SiteTree, raw_path: &Path,
+    config: &Config, languages: &Languages, lang: &str) -> std::io::Result<()> {
 
-    if analytics == true {
+    if config.features.analytics {
         /*
         file.write_all(format!(r#"
   <img src="https://stats.emile.space/count?p=/{}">
   <nav>
     <ul>"#, raw_path.to_str().unwrap()).as_bytes())?;
         */
-        file.write_all(format!(r#"
+        file.write_all(r#"
   <nav>
-    <ul>"#,).as_bytes())?;
+    <ul>"#.as_bytes())?;
     } else {
-        file.write_all(format!(r#"
+        file.write_all(r#"
   <nav>
-    <ul>"#).as_bytes())?;
+    <ul>"#.as_bytes())?;
     }
 
     // get the nav bar components
@@ -172,43 +769,32 @@ fn write_nav(file: &mut File, in_path: &Path, raw_path: &Path, analytics: bool)
     // for each list of components (["a"], ["a", "b"], ["a", "b", "c"]), create
     // the path for the list, view all other dirs at that path and write the
     // result to the file
-    let mut i = 0;
     let slice = components.as_slice();
 
     // for each navbar component
-    for component in slice {
+    for (i, component) in slice.iter().enumerate() {
 
         // get the items belonging to that navbar item
         // (["a"], ["a", "b"], ["a", "b", "c"])
         let subpath_components = &slice[..i+1];
-        i += 1;
-
-        let mut subpath_path = PathBuf::new();
-
-        // push the inpath, so we've got a basis from where we can read the
-        // subpath items
-        // subpath_path = inpath + ???
-        subpath_path.push(in_path);
 
         let mut nav_breadcrumb_link = PathBuf::new();
 
-        // for each item in the subpath, push it into the subpath_path so that
-        // in the end, we've got something like this:
-        // "inpath" + "a" + "b" + "c"
+        // for each item in the subpath, push it into the nav_breadcrumb_link
+        // so that in the end, we've got something like this: "a" + "b" + "c"
         for subpath_component in subpath_components {
-            subpath_path.push(subpath_component);
             nav_breadcrumb_link.push(subpath_component);
         }
 
-        // make the nav_breadcrumb_link an absolute by prefixing it with a /
-        // (this is in scope of the web-page, so this is find) and make it a
-        // string
-        let nav_breadcrumb_link_absolute 
-            = Path::new("/")
-                .join(nav_breadcrumb_link);
+        // the dirs on the same level as the breadcrumb: the entries of its
+        // parent
+        let parent = nav_breadcrumb_link.parent().unwrap().to_path_buf();
 
+        // make the nav_breadcrumb_link an absolute link to the page in the
+        // language of the current page (this is in scope of the web-page, so
+        // this is find)
         let nav_breadcrumb_link
-            = nav_breadcrumb_link_absolute.to_str().unwrap();
+            = languages.link(&nav_breadcrumb_link, lang);
 
         // define the name of the breadcrumb
         let nav_breadcrumb_name = component.as_os_str().to_str().unwrap();
@@ -220,38 +806,13 @@ fn write_nav(file: &mut File, in_path: &Path, raw_path: &Path, analytics: bool)
             <ul>"#, nav_breadcrumb_link, nav_breadcrumb_name).as_bytes())?;
         ////////////////////////////////////////////////////////////////////////
 
-        // as we don't want to get the items for the individial entry, but on
-        // the same level, we push a ".."
-        // the subpath_path is now: inpath + subpath + ../
-        subpath_path.push("..");
-
-        // read all dirs in the subpath_path, add them to the dirs vector, so
-        // that we get a vector containing all the dirs we want
-        let mut dirs = Vec::new();
-        for entry in fs::read_dir(subpath_path)? {
-            let path = &entry?.path();
-            if path.is_dir() {
-                dirs.push(path.to_path_buf());
-            }
-        }
-
-        dirs.sort();
-
         // DROPDOWN
-        // extract the link and name for each directory found
-        for dir in dirs {
-            let d = dir.canonicalize()?;
-            let abs_inpath = in_path.canonicalize()?;
+        // extract the link and name for each directory on the same level
+        for dir in tree.entries(&parent).iter().filter(|entry| entry.is_dir) {
+            let name = dir.name.as_str();
+            let rel_link = parent.join(name);
 
-            let name = d.file_name().unwrap().to_str().unwrap();
-            let rel_link 
-                = d.strip_prefix(abs_inpath)
-                    .expect(format!(
-                        "could not strip the in_path prefix: {:?}",
-                        d).as_str());
-
-            let link = Path::new("/").join(rel_link);
-            let link = link.as_path().to_str().unwrap();
+            let link = languages.link(&rel_link, lang);
 
             // don't add the current page to the dropdown, we're on it already!
             if name == nav_breadcrumb_name {
@@ -264,6 +825,12 @@ fn write_nav(file: &mut File, in_path: &Path, raw_path: &Path, analytics: bool)
                 continue
             }
 
+            // don't add ignored items to the dropdown, they're not part of
+            // the site
+            if dir.ignored {
+                continue
+            }
+
             ////////////////////////////////////////////////////////////////////
             file.write_all(format!(r#"
                 <li><a href="{}">{}/</a></li>"#, link, name).as_bytes())?;
@@ -277,132 +844,263 @@ fn write_nav(file: &mut File, in_path: &Path, raw_path: &Path, analytics: bool)
         ////////////////////////////////////////////////////////////////////////
     }
 
+    // the source of a translation isn't next to its page, so it is linked
+    // absolutely
+    let source_link = match lang == languages.default() {
+        true => "README.md".to_string(),
+        false => format!("{}/README.{}.md",
+            Path::new("/").join(raw_path).to_str().unwrap().trim_end_matches('/'),
+            lang),
+    };
+
     ////////////////////////////////////////////////////////////////////////////
     file.write_all(format!(r#"
     </ul>
     <ul style="float: right">
         <li>{:?}</li>
         <li>
-            <a href="README.md">.md</a>
-        </li>
+            <a href="{}">.md</a>
+        </li>"#, tree.modified().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs(),
+        source_link).as_bytes())?;
+    i18n::write_switcher(file, languages, raw_path, lang)?;
+    file.write_all(r#"
     </ul>
-  </nav>"#, in_path.metadata()?.modified()?.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()).as_bytes())?;
+  </nav>"#.as_bytes())?;
     ////////////////////////////////////////////////////////////////////////////
 
     Ok(())
 }
 
 
-fn write_same_level(file: &mut File, in_path: &Path, raw_path: &Path)
+fn write_same_level(file: &mut dyn Write, tree: &SiteTree, raw_path: &Path,
+    settings: &Settings, languages: &Languages, lang: &str)
     -> std::io::Result<()> {
 
-    let search_path = Path::new(in_path).join(raw_path);
+    let (dirs, files) = listed_entries(tree, raw_path, settings);
+
+    if settings.listing == Listing::Cards {
+        write_cards(file, tree, raw_path, &dirs, languages, lang)?;
+    } else {
+        if settings.listing == Listing::Vertical {
+            file.write_all(r#"
+  <ul class="vert">"#.as_bytes())?;
+        } else {
+            file.write_all(r#"
+  <ul>"#.as_bytes())?;
+        }
+
+        for dir in &dirs {
+            let link_str = languages.link(&raw_path.join(&dir.name), lang);
+            let name = dir.name.as_str();
+
+            if name.starts_with(".") {
+                continue
+            }
+
+            file.write_all(format!(r#"
+    <li><a href="{}">{}/</a></li>"#, link_str, name).as_bytes())?;
+        }
+
+        file.write_all(r#"
+  </ul>"#.as_bytes())?;
+    }
 
-    let mut dirs: Vec<PathBuf> = Vec::new();
-    let mut files: Vec<PathBuf> = Vec::new();
+    if !files.is_empty() && settings.show_files {
+        file.write_all(r#"<br>
+    <ul>"#.as_bytes())?;
 
-    let mut vertical: bool = false;
-    let mut show_files: bool = false;
+        for f in files {
+            let link = Path::new("/").join(raw_path).join(&f.name);
+            let link_str = link.as_path().to_str().unwrap();
+            let name = f.name.as_str();
 
-    for entry in fs::read_dir(search_path)? {
-        let path = &entry?.path();
+            if i18n::is_readme(name)
+                || name.starts_with(".")
+                {
+                continue
+            };
 
-        if path.is_dir() {
-            dirs.push(path.to_path_buf());
+            file.write_all(format!(r#"
+        <li><a href="{}">{}</a></li>"#, link_str, name).as_bytes())?;
         }
-        if path.is_file() {
-            files.push(path.to_path_buf());
-            if path.file_name().unwrap() == "vertical" {
-                vertical = true;
-            }
-            if path.file_name().unwrap() == "show_files" {
-                show_files = true;
-            }
+
+        file.write_all(r#"
+    </ul>"#.as_bytes())?;
+    }
+
+
+    Ok(())
+}
+
+/// the subdirectories and files listed on the page at `raw_path`, in the
+/// order of the settings
+fn listed_entries<'a>(tree: &'a SiteTree, raw_path: &Path, settings: &Settings)
+    -> (Vec<&'a tree::Entry>, Vec<&'a tree::Entry>) {
+
+    let mut dirs: Vec<&tree::Entry> = Vec::new();
+    let mut files: Vec<&tree::Entry> = Vec::new();
+
+    for entry in tree.entries(raw_path) {
+        if entry.ignored {
+            continue
+        }
+
+        if settings.is_hidden(&entry.name) {
+            continue
+        }
+
+        if entry.is_dir {
+            dirs.push(entry);
+        } else if !settings::is_settings_file(entry) {
+            files.push(entry);
         }
     }
 
-    dirs.sort();
-    files.sort();
+    sort_entries(&mut dirs, settings, tree, raw_path);
+    sort_entries(&mut files, settings, tree, raw_path);
 
-    let in_path = in_path.canonicalize()?;
+    (dirs, files)
+}
 
-    if vertical == true {
-        file.write_all(format!(r#"
-  <ul class="vert">"#).as_bytes())?;
+/// sort the directory entries as configured in the settings. The front matter
+/// of the README.md of directories is used for the date and title, if present.
+fn sort_entries(entries: &mut [&tree::Entry], settings: &Settings,
+    tree: &SiteTree, raw_path: &Path) {
+
+    let front_matter = |entry: &tree::Entry| if entry.is_dir {
+        tree.front_matter(&raw_path.join(&entry.name))
     } else {
-        file.write_all(format!(r#"
-  <ul>"#).as_bytes())?;
+        FrontMatter::default()
+    };
+
+    match settings.sort {
+        Sort::Name => entries.sort_by(|a, b| a.name.cmp(&b.name)),
+        Sort::Date => entries.sort_by_cached_key(|entry| {
+            let date = match front_matter(entry).get("date") {
+                Some(date) => date.to_string(),
+                None => format_date(entry.modified),
+            };
+            (date, entry.name.clone())
+        }),
+        Sort::Title => entries.sort_by_cached_key(|entry| {
+            let title = match front_matter(entry).get("title") {
+                Some(title) => title.to_string(),
+                None => entry.name.clone(),
+            };
+            (title.to_lowercase(), entry.name.clone())
+        }),
+    }
+
+    if settings.reverse {
+        entries.reverse();
     }
+}
+
+/// Write the subdirectories as cards showing the title, description, date and
+/// thumbnail from the front matter of their README.md
+fn write_cards(file: &mut dyn Write, tree: &SiteTree, raw_path: &Path,
+    dirs: &[&tree::Entry], languages: &Languages, lang: &str)
+    -> std::io::Result<()> {
+
+    file.write_all(r#"
+  <ul class="cards">"#.as_bytes())?;
 
     for dir in dirs {
-        let dir = dir.canonicalize()?;
-        let dir = dir.strip_prefix(&in_path)
-            .expect("could not strip in_path prefix");
+        let rel_dir = raw_path.join(&dir.name);
+        let rel_dir = rel_dir.as_path();
 
-        let link = Path::new("/").join(dir);
-        let link_str = link.as_path().to_str().unwrap();
+        // the thumbnail is next to the README.md, the page may be translated
+        let link = Path::new("/").join(rel_dir);
+        let link_str = languages.link(rel_dir, lang);
         let name = link.file_name().unwrap().to_str().unwrap();
 
         if name.starts_with(".") {
             continue
         }
 
-        file.write_all(format!(r#"
-    <li><a href="{}">{}/</a></li>"#, link_str, name).as_bytes())?;
-    }
-
-    file.write_all(format!(r#"
-  </ul>"#).as_bytes())?;
+        let front_matter = tree.front_matter(rel_dir);
+        let title = front_matter.get("title")
+            .map(String::from)
+            .unwrap_or(format!("{}/", name));
 
-    if files.len() >= 1 && show_files == true {
-        file.write_all(format!(r#"<br>
-    <ul>"#).as_bytes())?;
+        file.write_all(r#"
+    <li class="card">"#.as_bytes())?;
 
-        for f in files {
-            let f = f.canonicalize()?;
-            let f = f.strip_prefix(&in_path)
-                .expect("could not strip in_path prefix");
+        if let Some(thumbnail) = front_matter.get("thumbnail") {
+            file.write_all(format!(r#"
+      <a href="{}"><img src="{}" alt="" loading="lazy"/></a>"#,
+                link_str, escape_html(link.join(thumbnail).to_str().unwrap()),
+            ).as_bytes())?;
+        }
 
-            let link = Path::new("/").join(f);
-            let link_str = link.as_path().to_str().unwrap();
-            let name = link.file_name().unwrap().to_str().unwrap();
+        file.write_all(format!(r#"
+      <a href="{}">{}</a>"#, link_str, escape_html(&title)).as_bytes())?;
 
-            if name == "README.md"
-                || name == "show_files"
-                || name.starts_with(".")
-                {
-                continue
-            };
+        if let Some(date) = front_matter.get("date") {
+            file.write_all(format!(r#"
+      <time>{}</time>"#, escape_html(date)).as_bytes())?;
+        }
 
+        if let Some(description) = front_matter.get("description") {
             file.write_all(format!(r#"
-        <li><a href="{}">{}</a></li>"#, link_str, name).as_bytes())?;
+      <p>{}</p>"#, escape_html(description)).as_bytes())?;
         }
 
-        file.write_all(format!(r#"
-    </ul>"#).as_bytes())?;
+        file.write_all(r#"
+    </li>"#.as_bytes())?;
     }
 
-
-    Ok(())
+    file.write_all(r#"
+  </ul>"#.as_bytes())
 }
 
-fn write_readme_content(file: &mut File, in_path: &Path, raw_path: &Path) 
-    -> std::io::Result<()> {
+/// Write the content of the README.md (or the translation `readme_name`) as
+/// html, returning its front matter
+fn write_readme_content(file: &mut dyn Write, tree: &SiteTree, raw_path: &Path,
+    readme_name: &str, links: &LinkGraph, config: &Config,
+    dependencies: &Dependencies) -> std::io::Result<FrontMatter> {
 
     // define the path of the README.md file
-    let readme_file_path 
-        = Path::new(in_path).join(raw_path).join("README.md");
-
-    // open the file and read it as a string
-    let mut readme_file = File::open(readme_file_path)?;
-    let mut readme = String::new();
-    readme_file.read_to_string(&mut readme)?;
-
-    file.write_all(format!("<pre>").as_bytes())?;
-
-    // counting the occurrence of `---`
-    let mut hrule_count = 0;
-    let mut in_yaml_metadata_block= false;
+    let readme_rel_path = raw_path.join(readme_name);
+    let readme_file_path = tree.in_path().join(&readme_rel_path);
+
+    // the content was read when scanning the input path
+    let readme = tree.readme(&readme_rel_path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!(
+            "{:?} is not part of the site", readme_file_path)))?;
+    let modified = tree.entry(&readme_rel_path).unwrap().modified;
+
+    let translation = i18n::readme_language(readme_name).flatten();
+
+    // the front matter isn't part of the content, skip it
+    let (front_matter, content, front_matter_lines) = frontmatter::split(readme);
+
+    // the values of the `{{ variables }}` in the content
+    let variables = Variables::for_page(config, raw_path, &readme_file_path,
+        modified, &front_matter);
+
+    // substitute them up front, so that directives reading the content (like
+    // the table of contents) see the same headings as the rendered page. Code
+    // lines are written as they are, they may contain braces of their own.
+    let content = content.split('\n')
+        .enumerate()
+        .map(|(line_number, line)| match line.starts_with("> ") {
+            true => Ok(line.to_string()),
+            false => variables.substitute(line)
+                .map_err(|msg| io::Error::new(io::ErrorKind::InvalidData, format!(
+                    "{}:{}: {}", readme_file_path.display(),
+                    front_matter_lines + line_number + 1, msg))),
+        })
+        .collect::<io::Result<Vec<_>>>()?
+        .join("\n");
+
+    // translations aren't rendered next to the files they link to
+    let content = match translation {
+        Some(_) => i18n::absolute_links(&content, raw_path),
+        None => content,
+    };
+
+    file.write_all("<pre>".as_bytes())?;
 
     let mut level_1_heading_num = 0;
     let mut level_2_heading_num = 0;
@@ -410,40 +1108,27 @@ fn write_readme_content(file: &mut File, in_path: &Path, raw_path: &Path)
     let mut level_4_heading_num = 0;
     let mut level_5_heading_num = 0;
 
-    // cheap markdown 2 html converter
-    for line in readme.split('\n') {
+    // the context the directives in the readme are rendered in
+    let ctx = directive::Context {
+        in_path: tree.in_path(),
+        raw_path,
+        tree,
+        readme: &content,
+        readme_path: &readme_file_path,
+        dependencies,
+        links,
+    };
 
-        // 1 == 2, as I'm not sure how to comment out the file write 5 lines or so below
-        if in_yaml_metadata_block && 1 == 2 {
-            // if we find the end of the yaml metadata block, break this
-            if line.starts_with("---") {
-                in_yaml_metadata_block = false;
-                continue
-            } else {
-                file.write_all(format!(r##"yaml_line: {}
-"##, line).as_bytes())?;
-                continue
-            }
-        }
+    // cheap markdown 2 html converter
+    for (line_number, line) in content.split('\n').enumerate() {
 
-        // if we've got a horizontal rule, it can be two things: the start and
-        // end of a yaml-metadata block or an actual horizontal rule.
-        //
-        // If it's yaml metadata, read it all, but don't print it, store it
-        // for later
-        // If it's a horizontal rule, print the horizontal rule
+        // the yaml metadata block has already been split off, so this is an
+        // actual horizontal rule
         if line.starts_with("---") {
 
-            // store the yaml metadata
-            if hrule_count == 0 {
-                in_yaml_metadata_block = true;
-                continue
-            }                 
-            hrule_count += 1;
-
             // print the horizontal rule
-            file.write_all(format!(r##"
-            <hr>"##).as_bytes())?;
+            file.write_all(r##"
+            <hr>"##.as_bytes())?;
 
         } else if line.starts_with("#####") {
             let heading = line.get(6..).unwrap();
@@ -545,266 +1230,371 @@ fn write_readme_content(file: &mut File, in_path: &Path, raw_path: &Path)
             let line = line.get(2..).unwrap();
             file.write_all(format!("</pre><pre class=\"code\">{}</pre><pre>\n", line).as_bytes())?;
 
-        } else if line.starts_with(":::tree") {
+        } else if directive::is_directive(line) {
 
-            // TODO: add some parameter controlling if the list is ascending or descending (reverse the list before writing)
+            directive::write_directive(file, &ctx, line,
+                front_matter_lines + line_number + 1)?;
 
-            // get all dirs in the current dir recursively
-            let tree_files_path = Path::new(in_path).join(raw_path);
-            let mut tree_files
-                = recursive_read_dir(&tree_files_path, true)?;
+        } else {
 
-            // sort them, otherwise we'll get complete chaos
-            tree_files.sort();
+            // for the case that nothing of the above matches, just write the
+            // content into the html body as it is
+            file.write_all(format!("{}\n", line).as_bytes())?;
+        }
+    }
 
-            for path in tree_files {
-                
-                // strip the inpath prefix and raw_path prefix, as we don't need
-                // them
-                let path 
-                    = path.strip_prefix(in_path)
-                        .expect("could not strip in_file prefix")
-                        .strip_prefix(raw_path)
-                        .expect("could not strip raw_path prefix");
+    Ok(front_matter)
+}
 
-                // convert the path to a string, check if it contains a hidden
-                // path by checking if it contains a `/.`, if so, skip this one
-                if String::from(path.to_str().unwrap()).contains("/.") {
-                    continue
-                }
-                if String::from(path.to_str().unwrap()).starts_with(".") {
-                    continue
+/// Write a tree of all directories below the current page.
+///
+/// Flags:
+///   - `reverse`: list the directories on each level in descending order
+fn write_tree(file: &mut dyn Write, ctx: &directive::Context,
+    directive: &directive::Directive) -> std::io::Result<()> {
+
+    let in_path = ctx.in_path;
+    let raw_path = ctx.raw_path;
+
+    // get all dirs in the current dir recursively
+    let tree_files_path = Path::new(in_path).join(raw_path);
+    let mut tree_files = ctx.tree.pathes(raw_path, true);
+    ctx.dependencies.dir(&tree_files_path);
+    for dir in &tree_files {
+        ctx.dependencies.dir(dir);
+    }
+
+    // sort them, otherwise we'll get complete chaos. When reversing, only the
+    // order of the siblings is reversed, parents are still listed before
+    // their children
+    if directive.flag("reverse") {
+        tree_files.sort_by(|a, b| {
+            for (a, b) in a.components().zip(b.components()) {
+                if a != b {
+                    return b.cmp(&a);
                 }
-                println!("[i] {:?}", path);
+            }
+            a.components().count().cmp(&b.components().count())
+        });
+    } else {
+        tree_files.sort();
+    }
 
-                // write the link and the entry name to the file
-                let link = Path::new(raw_path).join(path);
-                let name = path.file_name().unwrap().to_str().unwrap();
+    for path in tree_files {
+        
+        // strip the inpath prefix and raw_path prefix, as we don't need
+        // them
+        let path 
+            = path.strip_prefix(in_path)
+                .expect("could not strip in_file prefix")
+                .strip_prefix(raw_path)
+                .expect("could not strip raw_path prefix");
+
+        // convert the path to a string, check if it contains a hidden
+        // path by checking if it contains a `/.`, if so, skip this one
+        if String::from(path.to_str().unwrap()).contains("/.") {
+            continue
+        }
+        if String::from(path.to_str().unwrap()).starts_with(".") {
+            continue
+        }
 
-                // count the amount of segments in the path and write spaces for
-                // each
-                let segments = path.iter().count();
-                for _ in 0..(segments-1) {
-                    file.write_all(r#"    "#.as_bytes())?;
-                }
+        // write the link and the entry name to the file
+        let link = Path::new(raw_path).join(path);
+        let name = path.file_name().unwrap().to_str().unwrap();
 
-                file.write_all(
-                    format!("<a href=\"/{}\">{}</a>\n",
-                        link.display(), name, 
-                        ).as_bytes()
-                )?;
-            }
+        // count the amount of segments in the path and write spaces for
+        // each
+        let segments = path.iter().count();
+        for _ in 0..(segments-1) {
+            file.write_all(r#"    "#.as_bytes())?;
+        }
+
+        file.write_all(
+            format!("<a href=\"/{}\">{}</a>\n",
+                link.display(), name, 
+                ).as_bytes()
+        )?;
+    }
+
+    Ok(())
+}
 
-        } else if line.starts_with(":::toc") {
-
-            // TODO: depth parameter for controlling the depth of the table of contents
-
-            let mut level_1_num = 0;
-            let mut level_2_num = 0;
-            let mut level_3_num = 0;
-            let mut level_4_num = 0;
-            let mut level_5_num = 0;
-
-            for line in readme.split('\n') {
-                if line.starts_with("#####") {
-                    let line = line.get(6..).unwrap();
-                    // trim the line to remove the trailing whitespace
-                    let line = line.trim();
-                    level_5_num += 1;
-                    file.write_all(
-                        format!(
-                            r##"           <a href="#{}">{}.{}.{}.{}.{}. {}</a>
+/// Write a table of contents of the headings of the current page.
+///
+/// Options:
+///   - `depth=n`: only include headings up to level n (default: 5)
+fn write_toc(file: &mut dyn Write, ctx: &directive::Context,
+    directive: &directive::Directive) -> std::io::Result<()> {
+
+    let depth = match directive.option("depth") {
+        Some(depth) => match depth.parse::<usize>() {
+            Ok(depth) if (1..=5).contains(&depth) => depth,
+            _ => return Err(directive::invalid_input(format!(
+                "expected a toc depth between 1 and 5, got {:?}", depth))),
+        },
+        None => 5,
+    };
+
+    let mut level_1_num = 0;
+    let mut level_2_num = 0;
+    let mut level_3_num = 0;
+    let mut level_4_num = 0;
+    let mut level_5_num = 0;
+
+    for line in ctx.readme.split('\n') {
+        if line.starts_with("#####") {
+            let line = line.get(6..).unwrap();
+            // trim the line to remove the trailing whitespace
+            let line = line.trim();
+            level_5_num += 1;
+            if depth < 5 {
+                continue
+            }
+            file.write_all(
+                format!(
+                    r##"           <a href="#{}">{}.{}.{}.{}.{}. {}</a>
 "##,
-                            sanitize(line.to_string()),
-                            level_1_num,
-                            level_2_num,
-                            level_3_num,
-                            level_4_num,
-                            level_5_num,
-                            line
-                        ).as_bytes()
-                    )?;
-                } else if line.starts_with("####") {
-                    let line = line.get(5..).unwrap();
-                    // trim the line to remove the trailing whitespace
-                    let line = line.trim();
-                    level_4_num += 1;
-                    level_5_num = 0;
-                    file.write_all(
-                        format!(
-                            r##"         <a href="#{}">{}.{}.{}.{}. {}</a>
+                    sanitize(line.to_string()),
+                    level_1_num,
+                    level_2_num,
+                    level_3_num,
+                    level_4_num,
+                    level_5_num,
+                    line
+                ).as_bytes()
+            )?;
+        } else if line.starts_with("####") {
+            let line = line.get(5..).unwrap();
+            // trim the line to remove the trailing whitespace
+            let line = line.trim();
+            level_4_num += 1;
+            level_5_num = 0;
+            if depth < 4 {
+                continue
+            }
+            file.write_all(
+                format!(
+                    r##"         <a href="#{}">{}.{}.{}.{}. {}</a>
 "##,
-                            sanitize(line.to_string()),
-                            level_1_num,
-                            level_2_num,
-                            level_3_num,
-                            level_4_num,
-                            line
-                        ).as_bytes()
-                    )?;
-                } else if line.starts_with("###") {
-                    let line = line.get(4..).unwrap();
-                    // trim the line to remove the trailing whitespace
-                    let line = line.trim();
-                    level_3_num += 1;
-                    level_4_num = 0;
-                    level_5_num = 0;
-                    file.write_all(
-                        format!(
-                            r##"       <a href="#{}">{}.{}.{}. {}</a>
+                    sanitize(line.to_string()),
+                    level_1_num,
+                    level_2_num,
+                    level_3_num,
+                    level_4_num,
+                    line
+                ).as_bytes()
+            )?;
+        } else if line.starts_with("###") {
+            let line = line.get(4..).unwrap();
+            // trim the line to remove the trailing whitespace
+            let line = line.trim();
+            level_3_num += 1;
+            level_4_num = 0;
+            level_5_num = 0;
+            if depth < 3 {
+                continue
+            }
+            file.write_all(
+                format!(
+                    r##"       <a href="#{}">{}.{}.{}. {}</a>
 "##,
-                            sanitize(line.to_string()),
-                            level_1_num,
-                            level_2_num,
-                            level_3_num,
-                            line
-                        ).as_bytes()
-                    )?;
-                } else if line.starts_with("##") {
-                    let line = line.get(3..).unwrap();
-                    let line = line.trim();
-                    level_2_num += 1;
-                    level_3_num = 0;
-                    level_4_num = 0;
-                    level_5_num = 0;
-
-                    file.write_all(
-                        format!(
-                            //r##"    <a href="#{}">{}.{}. {}</a>
-                            r##"    <a href="#{}">{}.{}. {}</a>
+                    sanitize(line.to_string()),
+                    level_1_num,
+                    level_2_num,
+                    level_3_num,
+                    line
+                ).as_bytes()
+            )?;
+        } else if line.starts_with("##") {
+            let line = line.get(3..).unwrap();
+            let line = line.trim();
+            level_2_num += 1;
+            level_3_num = 0;
+            level_4_num = 0;
+            level_5_num = 0;
+
+            if depth < 2 {
+                continue
+            }
+            file.write_all(
+                format!(
+                    //r##"    <a href="#{}">{}.{}. {}</a>
+                    r##"    <a href="#{}">{}.{}. {}</a>
 "##,
-                            sanitize(line.to_string()),
-                            level_1_num,
-                            level_2_num,
-                            line
-                        ).as_bytes()
-                    )?;
-                } else if line.starts_with("#") {
-                    let line = line.get(2..).unwrap();
-                    let line = line.trim();
-                    level_1_num += 1;
-                    level_2_num = 0;
-                    level_3_num = 0;
-                    level_4_num = 0;
-                    level_5_num = 0;
-
-                    file.write_all(
-                        format!(
-                            r##"<a href="#{}">{}. {}</a>
+                    sanitize(line.to_string()),
+                    level_1_num,
+                    level_2_num,
+                    line
+                ).as_bytes()
+            )?;
+        } else if line.starts_with("#") {
+            let line = line.get(2..).unwrap();
+            let line = line.trim();
+            level_1_num += 1;
+            level_2_num = 0;
+            level_3_num = 0;
+            level_4_num = 0;
+            level_5_num = 0;
+
+            file.write_all(
+                format!(
+                    r##"<a href="#{}">{}. {}</a>
 "##,
-                            sanitize(line.to_string()),
-                            level_1_num,
-                            line
-                        ).as_bytes()
-                    )?;
-                }
-            }
-
-        } else {
-
-            // for the case that nothing of the above matches, just write the
-            // content into the html body as it is
-            file.write_all(format!("{}\n", line).as_bytes())?;
+                    sanitize(line.to_string()),
+                    level_1_num,
+                    line
+                ).as_bytes()
+            )?;
         }
     }
 
     Ok(())
 }
 
-fn write_footer(file: &mut File) -> std::io::Result<()> {
-    file.write_all(format!(r#"<br>
+/// Write the footer: the links and webrings from the config and the author
+/// line
+fn write_footer(file: &mut dyn Write, config: &Config,
+    build_time: Option<time::SystemTime>) -> std::io::Result<()> {
+    file.write_all(r#"<br>
     <br>
     <br>
-    </pre>
-<a href="https://chaos.social/@hanemile.rss" target="_blank" rel="noopener" class="icon"><img class="webring" src="/rss.svg" alt="rss feed of @hanemile@chaos.social mastodon" height="32px"/></a>
-<a href="https://lieu.cblgh.org/" target="_blank" rel="noopener" class="icon"><img class="webring" src="/lieu.svg" alt="lieu webring search engine" height="32px"/></a>
-<a href="https://webring.xxiivv.com/#emile" target="_blank" rel="noopener" class="icon"><img class="webring" src="/webring.svg" alt="XXIIVV webring" height="32px"/></a>
-<a rel="me" href="https://chaos.social/@hanemile" target="_blank" class="icon"><img class="webring" src="/mastodon.svg" alt="mastodon" height="32px"/></a>
-    <pre>emile - {:?} - generated using <a href="https://github.com/hanemile/vokobe">vokobe {:?}</a><pre>
-</body>
-</html>
-"#,
-    time::SystemTime::now().duration_since(time::SystemTime::UNIX_EPOCH).unwrap(),
+    </pre>"#.as_bytes())?;
+
+    for link in &config.footer.links {
+        write_footer_link(file, &link.url, &link.name, link.icon.as_deref(),
+            link.rel.as_deref())?;
+    }
+
+    for webring in &config.footer.webrings {
+        if let Some(prev) = &webring.prev {
+            file.write_all(format!(r#"
+<a href="{}" target="_blank" rel="noopener" class="webring-prev">&larr;</a>"#,
+                escape_html(prev)).as_bytes())?;
+        }
+
+        write_footer_link(file, &webring.url, &webring.name,
+            webring.icon.as_deref(), None)?;
+
+        if let Some(next) = &webring.next {
+            file.write_all(format!(r#"
+<a href="{}" target="_blank" rel="noopener" class="webring-next">&rarr;</a>"#,
+                escape_html(next)).as_bytes())?;
+        }
+    }
+
+    // the copyright line replaces the author
+    let author = config.footer.copyright.as_ref().or(config.author.as_ref());
+    let author = match author {
+        Some(author) => format!("{} - ", escape_html(author)),
+        None => String::new(),
+    };
+
+    // the time of the build is only shown if asked for, so that building the
+    // same input twice gives the same output
+    let build_time = match build_time {
+        Some(build_time) => format!("{:?} - ",
+            build_time.duration_since(time::SystemTime::UNIX_EPOCH).unwrap()),
+        None => String::new(),
+    };
+
+    file.write_all(format!(r#"
+    <pre>{}{}generated using <a href="https://github.com/hanemile/vokobe">vokobe {:?}</a><pre>"#,
+    author,
+    build_time,
     env!("CARGO_PKG_VERSION")
     ).as_bytes())?;
 
     Ok(())
 }
 
-/// sanitize the given string (to lower + space to hypen + keep only
-/// [a-zA-Z0-9])
-fn sanitize(input: String) -> String {
-    let input = input.replace(" ", "-");
-
-    input
-        .chars()
-        .filter(|c| c.is_ascii_alphanumeric() || c.eq(&'-'))
-        .collect::<String>()
-        .to_lowercase()
-}
+/// Write a single footer link, as icon if one is given and as text otherwise
+fn write_footer_link(file: &mut dyn Write, url: &str, name: &str,
+    icon: Option<&str>, rel: Option<&str>) -> std::io::Result<()> {
 
-/// Return a list of all files in the directory, recursively.
-fn recursive_read_dir(dir: &PathBuf, dir_only: bool) -> io::Result<Vec<PathBuf>> {
+    let rel = rel.unwrap_or("noopener");
 
-    // return an empty vec if the given path is not a directory
-    if dir.is_dir() == false {
-        return Ok(vec![]);
+    match icon {
+        Some(icon) => file.write_all(format!(r#"
+<a href="{}" target="_blank" rel="{}" class="icon"><img class="webring" src="{}" alt="{}" height="32px"/></a>"#,
+            escape_html(url), escape_html(rel), escape_html(icon),
+            escape_html(name)).as_bytes()),
+        None => file.write_all(format!(r#"
+<a href="{}" target="_blank" rel="{}">{}</a>"#,
+            escape_html(url), escape_html(rel), escape_html(name)).as_bytes()),
     }
+}
 
-    if dir.starts_with(".") {
-       return Ok(vec![]); 
+/// the 64 bit FNV-1a hash of the given data as hex string. Not cryptographic,
+/// but stable across builds and platforms.
+fn content_hash(data: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
     }
+    format!("{:016x}", hash)
+}
 
-        // get all entries in the gitignore file, if it exists
-    let gitignore_entries: Vec<PathBuf> = gitignore_entries(&dir)?;
+/// the time given in $SOURCE_DATE_EPOCH (seconds since the epoch), see
+/// https://reproducible-builds.org/specs/source-date-epoch/
+fn source_date_epoch() -> io::Result<Option<time::SystemTime>> {
+    let value = match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(value) if !value.is_empty() => value,
+        _ => return Ok(None),
+    };
 
-    // store the child pathes
-    let mut entries: Vec<PathBuf> = Vec::new();
-    
-    // iterate over all items in the dir, pushing the dirs pathes to the dirs
-    // vector for returning it
-    'outer: for entry in fs::read_dir(dir)? {
-        let dir_entry = &entry?;
-        let path = dir_entry.path();
-
-        // skip hidden folders
-        if path.starts_with(".") {
-            //continue 'outer;
-            break 'outer;
-        }
-        if dir.starts_with(".") {
-            //continue 'outer;
-            break 'outer;
-        }
+    let secs = value.trim().parse::<u64>()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!(
+            "SOURCE_DATE_EPOCH {:?} is not a number of seconds since the \
+            epoch", value)))?;
 
-        // check if the current entry is part of the gitignore, if so, skip it
-        for gitignore_entry in &gitignore_entries {
-            if gitignore_entry.to_str() == Some("") {
-                continue;
-            }
-            if path.ends_with(gitignore_entry) {
-                continue 'outer;
-            }
-        }
+    Ok(Some(time::UNIX_EPOCH + time::Duration::from_secs(secs)))
+}
 
-        if dir_only == true {
-            if path.is_dir() {
-                entries.push(path.to_path_buf());
-            }
-        } else {
-            entries.push(path.to_path_buf());
-        }
+/// format the given time as date (YYYY-MM-DD, UTC)
+fn format_date(time: time::SystemTime) -> String {
+    let secs = time.duration_since(time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+
+    // convert the days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = secs.div_euclid(86400) + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+        - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4
+        - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
 
-        // recursively push all dirs from all children to the dirs vector
-        let subdirs = recursive_read_dir(&path, dir_only)?;
+/// sanitize the given string (to lower + space to hypen + keep only
+/// [a-zA-Z0-9])
+fn sanitize(input: String) -> String {
+    let input = input.replace(" ", "-");
 
-        for subdir in subdirs {
-            entries.push(subdir)
-        }
-    }
+    input
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || c.eq(&'-'))
+        .collect::<String>()
+        .to_lowercase()
+}
 
-    // return the dirs, the ones from this folder and the ones from all child folders
-    Ok(entries)
+/// escape the characters that have a special meaning in html, so that the
+/// given string can be used as text or attribute value
+fn escape_html(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
 
 // try to open the gitignore file and read all entries from there.
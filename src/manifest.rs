@@ -0,0 +1,122 @@
+/*
+the manifest: a list of all files a build wrote to the output path, stored
+next to the output path (`.out.manifest` for `out`). Files listed in the
+manifest of the last build that weren't produced again have lost their source
+(a deleted or renamed page, a removed image, an outdated stylesheet bundle,
+...) and are removed from the output. Files in the output path vokobe didn't
+write are never touched.
+*/
+
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+use std::io::{self, BufRead, BufReader, Write};
+use std::fs::{self, File};
+
+/// the suffix of the manifest next to the output path
+pub const MANIFEST_SUFFIX: &str = ".manifest";
+
+pub struct Manifest {
+    output_path: PathBuf,
+
+    /// the manifest file
+    path: PathBuf,
+
+    /// the written files relative to the output path
+    files: BTreeSet<PathBuf>,
+}
+
+impl Manifest {
+
+    /// an empty manifest for the given output path stored at `path`
+    pub fn new(output_path: &Path, path: &Path) -> Manifest {
+        Manifest {
+            output_path: output_path.to_path_buf(),
+            path: path.to_path_buf(),
+            files: BTreeSet::new(),
+        }
+    }
+
+    /// load the manifest of the last build for the output path from `path`,
+    /// an empty one if there is none
+    pub fn load(output_path: &Path, path: &Path) -> io::Result<Manifest> {
+        let mut manifest = Manifest::new(output_path, path);
+
+        let manifest_file = match File::open(path) {
+            Ok(manifest_file) => manifest_file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(manifest),
+            Err(e) => return Err(e),
+        };
+
+        for line in BufReader::new(manifest_file).lines() {
+            let line = line?;
+            if !line.is_empty() {
+                manifest.files.insert(PathBuf::from(line));
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    /// add the file at the given path in the output path
+    pub fn add(&mut self, path: &Path) {
+        let rel_path = path.strip_prefix(&self.output_path)
+            .expect("could not strip the output path prefix");
+        self.files.insert(rel_path.to_path_buf());
+    }
+
+    /// the files of the last build that weren't written again
+    pub fn stale<'a>(&'a self, last: &'a Manifest) -> impl Iterator<Item = &'a PathBuf> {
+        last.files.difference(&self.files)
+    }
+
+    /// remove the stale files of the last build and the directories left
+    /// empty. Returns the removed files.
+    pub fn prune(&self, last: &Manifest) -> io::Result<Vec<PathBuf>> {
+        let mut removed = Vec::new();
+
+        for rel_path in self.stale(last) {
+            // the manifest of a build may only point into its output path
+            if rel_path.is_absolute() || rel_path.components()
+                .any(|c| c == Component::ParentDir) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                    "{}: {:?} is not inside the output path",
+                    last.path.display(), rel_path)));
+            }
+
+            let path = self.output_path.join(rel_path);
+            match fs::remove_file(&path) {
+                Ok(()) => removed.push(path.clone()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            }
+
+            // remove the directories that only contained the removed file
+            let mut dir = path.parent();
+            while let Some(current) = dir {
+                if current == self.output_path || fs::remove_dir(current).is_err() {
+                    break
+                }
+                dir = current.parent();
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// keep the stale files of the last build in the manifest, so that they
+    /// are removed by a later build
+    pub fn keep_stale(&mut self, last: &Manifest) {
+        let stale: Vec<PathBuf> = self.stale(last).cloned().collect();
+        self.files.extend(stale);
+    }
+
+    /// write the manifest
+    pub fn save(&self) -> io::Result<()> {
+        let mut manifest_file = crate::create_file(&self.path)?;
+        for rel_path in &self.files {
+            writeln!(manifest_file, "{}", rel_path.display())?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+/*
+atomic publishing: a build writes into a staging directory next to the output
+path and swaps it with the output path only if it succeeded, so that a web
+server never serves a half-built site and a failed build doesn't leave a
+broken mix behind. The staging directory starts as a copy of the output made
+of hard links, so that incremental builds only have to write what changed.
+Files in it are therefore never written to, but replaced (see
+`create_file`).
+*/
+
+use std::path::{Path, PathBuf};
+use std::io;
+use std::fs;
+
+pub struct Staging {
+    output_path: PathBuf,
+    staging_path: PathBuf,
+}
+
+impl Staging {
+
+    /// create the staging directory for the output path as a copy of the
+    /// current output, removing the leftovers of an earlier failed build
+    pub fn new(output_path: &Path) -> io::Result<Staging> {
+        let staging_path = crate::sibling_path(output_path, ".staging")?;
+
+        if staging_path.exists() {
+            fs::remove_dir_all(&staging_path)?;
+        }
+
+        if output_path.is_dir() {
+            link_tree(output_path, &staging_path)?;
+        } else {
+            fs::create_dir_all(&staging_path)?;
+        }
+
+        Ok(Staging {
+            output_path: output_path.to_path_buf(),
+            staging_path,
+        })
+    }
+
+    /// the directory the build writes to
+    pub fn path(&self) -> &Path {
+        &self.staging_path
+    }
+
+    /// swap the staging directory into place and remove the old output
+    pub fn publish(self) -> io::Result<()> {
+        if !self.output_path.exists() {
+            return fs::rename(&self.staging_path, &self.output_path);
+        }
+
+        match exchange(&self.staging_path, &self.output_path) {
+            Ok(()) => {},
+
+            // without support for atomic exchanges, the output is missing for
+            // the time between two renames
+            Err(_) => {
+                let mut old_name = self.staging_path.as_os_str().to_os_string();
+                old_name.push(".old");
+                let old_path = PathBuf::from(old_name);
+
+                fs::rename(&self.output_path, &old_path)?;
+                fs::rename(&self.staging_path, &self.output_path)?;
+                fs::rename(&old_path, &self.staging_path)?;
+            },
+        }
+
+        fs::remove_dir_all(&self.staging_path)
+    }
+
+    /// remove the staging directory after a failed build, the output stays
+    /// untouched
+    pub fn discard(self) -> io::Result<()> {
+        fs::remove_dir_all(&self.staging_path)
+    }
+}
+
+/// recreate the directory tree at `src` at `dst`, hard linking the files.
+/// Files that can't be linked (e.g. on another filesystem) are copied.
+fn link_tree(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let src_path = entry.path();
+        let dst_path = dst.join(entry.file_name());
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            link_tree(&src_path, &dst_path)?;
+        } else if file_type.is_symlink() {
+            copy_symlink(&src_path, &dst_path)?;
+        } else if fs::hard_link(&src_path, &dst_path).is_err() {
+            fs::copy(&src_path, &dst_path)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(src: &Path, dst: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(src)?, dst)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::copy(src, dst).map(|_| ())
+}
+
+/// atomically exchange the directories at the given pathes
+#[cfg(target_os = "linux")]
+fn exchange(a: &Path, b: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let a = CString::new(a.as_os_str().as_bytes())?;
+    let b = CString::new(b.as_os_str().as_bytes())?;
+
+    // renameat2 isn't wrapped by all libcs, so the syscall is used directly
+    let result = unsafe {
+        libc::syscall(libc::SYS_renameat2,
+            libc::AT_FDCWD, a.as_ptr(),
+            libc::AT_FDCWD, b.as_ptr(),
+            libc::RENAME_EXCHANGE)
+    };
+
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn exchange(_a: &Path, _b: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported,
+        "atomic exchanges are only supported on linux"))
+}
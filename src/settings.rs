@@ -0,0 +1,206 @@
+/*
+per directory settings, read from a .dir.toml in the directory. Settings are
+inherited by all subdirectories unless they override them:
+
+    # how the subdirectories are listed: horizontal, vertical or cards
+    listing = "cards"
+
+    # also list the files in the directory
+    show_files = true
+
+    # the order of the listed entries: name, date or title
+    sort = "date"
+    reverse = true
+
+    # glob patterns of entries not shown in the listing
+    hidden = ["drafts", "*.bak"]
+
+    # the template the page is rendered with (templates/<name>.html)
+    template = "post"
+
+    # the parts of the page that are shown: page (everything), post (no
+    # listing) or slides (only the content). Can be overridden per page with
+    # `layout:` in the front matter.
+    layout = "post"
+
+The empty marker files `vertical`, `show_files` and `cards` are still
+supported, they only apply to the directory they are in.
+*/
+
+use std::path::{Path, PathBuf};
+use std::io::{self, Read};
+use std::fs::File;
+
+use serde::Deserialize;
+use serde::de::IntoDeserializer;
+
+use crate::config::glob_match;
+use crate::tree::{Entry, SiteTree};
+
+/// the name of the settings file in each directory
+pub const SETTINGS_FILE: &str = ".dir.toml";
+
+/// the legacy marker files changing the settings of their directory
+pub const MARKER_FILES: [&str; 3] = ["vertical", "show_files", "cards"];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Listing {
+    #[default]
+    Horizontal,
+    Vertical,
+    Cards,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    #[default]
+    Name,
+    /// the `date` from the front matter, the modification time otherwise
+    Date,
+    /// the `title` from the front matter, the name otherwise
+    Title,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    /// the header, navigation, listing, content and footer
+    #[default]
+    Page,
+    /// everything but the listing of the subdirectories
+    Post,
+    /// only the content
+    Slides,
+}
+
+impl Layout {
+
+    /// parse the name of a layout, e.g. from the front matter
+    pub fn parse(name: &str) -> io::Result<Layout> {
+        Layout::deserialize(name.into_deserializer())
+            .map_err(|e: serde::de::value::Error| io::Error::new(
+                io::ErrorKind::InvalidData, format!("invalid layout: {}", e)))
+    }
+
+    /// the parts of the page shown besides the content
+    pub fn parts(&self) -> &'static [&'static str] {
+        match self {
+            Layout::Page => &["header", "nav", "listing", "footer"],
+            Layout::Post => &["header", "nav", "footer"],
+            Layout::Slides => &[],
+        }
+    }
+}
+
+/// the content of a single settings file, unset values are inherited
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SettingsFile {
+    listing: Option<Listing>,
+    show_files: Option<bool>,
+    sort: Option<Sort>,
+    reverse: Option<bool>,
+    hidden: Option<Vec<String>>,
+    template: Option<String>,
+    layout: Option<Layout>,
+}
+
+impl SettingsFile {
+
+    /// read the settings file at the given path
+    pub fn read(path: &Path) -> io::Result<SettingsFile> {
+        let mut source = String::new();
+        File::open(path)?.read_to_string(&mut source)?;
+
+        toml::from_str(&source)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), e)))
+    }
+}
+
+/// the effective settings of a directory
+#[derive(Debug, Default, Clone)]
+pub struct Settings {
+    pub listing: Listing,
+    pub show_files: bool,
+    pub sort: Sort,
+    pub reverse: bool,
+    pub hidden: Vec<String>,
+    pub template: Option<String>,
+    pub layout: Layout,
+}
+
+impl Settings {
+
+    /// the settings of the directory `raw_path`: the settings files of all
+    /// its ancestors starting at the input path merged with its own settings
+    /// file and marker files
+    pub fn for_dir(tree: &SiteTree, raw_path: &Path) -> Settings {
+        let mut settings = Settings::default();
+
+        let mut dir = PathBuf::new();
+        settings.merge(tree.settings_file(&dir));
+        for component in raw_path.components() {
+            dir.push(component);
+            settings.merge(tree.settings_file(&dir));
+        }
+
+        // the marker files aren't inherited
+        let is_file = |name| tree.entry(&dir.join(name))
+            .is_some_and(|entry| entry.is_file);
+        if is_file("vertical") {
+            settings.listing = Listing::Vertical;
+        }
+        if is_file("cards") {
+            settings.listing = Listing::Cards;
+        }
+        if is_file("show_files") {
+            settings.show_files = true;
+        }
+
+        settings
+    }
+
+    fn merge(&mut self, file: Option<&SettingsFile>) {
+        let file = match file {
+            Some(file) => file,
+            None => return,
+        };
+
+        if let Some(listing) = file.listing {
+            self.listing = listing;
+        }
+        if let Some(show_files) = file.show_files {
+            self.show_files = show_files;
+        }
+        if let Some(sort) = file.sort {
+            self.sort = sort;
+        }
+        if let Some(reverse) = file.reverse {
+            self.reverse = reverse;
+        }
+        if let Some(hidden) = &file.hidden {
+            self.hidden = hidden.clone();
+        }
+        if let Some(template) = &file.template {
+            self.template = Some(template.clone());
+        }
+        if let Some(layout) = file.layout {
+            self.layout = layout;
+        }
+    }
+
+    /// whether the entry with the given name is hidden from the listing
+    pub fn is_hidden(&self, name: &str) -> bool {
+        self.hidden.iter().any(|pattern| glob_match(pattern, name))
+    }
+}
+
+/// whether the entry is a settings or (empty) marker file. These configure
+/// the site and aren't part of it.
+pub fn is_settings_file(entry: &Entry) -> bool {
+    entry.name == SETTINGS_FILE
+        || (MARKER_FILES.contains(&entry.name.as_str()) && entry.size == 0)
+}
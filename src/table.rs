@@ -0,0 +1,287 @@
+/*
+the `:::table` directive: render a csv file or a json array of objects next to
+the README as a html table
+*/
+
+use std::path::{Component, Path, PathBuf};
+use std::io::{self, Read, Write};
+use std::fs::File;
+use std::cmp::Ordering;
+
+use crate::directive::{invalid_input, Context, Directive};
+use crate::escape_html;
+
+/// a table read from a data file: the column names and the rows
+struct Table {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+/// Write the data file given as argument as a html table to the file.
+///
+/// Options:
+///   - `columns=a,b,c`: only show the given columns in the given order
+///   - `sort=column`: sort the rows by the given column
+///   - `reverse`: reverse the order of the rows
+///   - `rename=column:Name`: show `Name` as header of `column`, can be given
+///     multiple times
+pub fn write_table(file: &mut dyn Write, ctx: &Context, directive: &Directive)
+    -> io::Result<()> {
+
+    let data_file = &directive.args[0];
+
+    let columns: Option<Vec<&str>> = directive.option("columns")
+        .map(|value| value.split(',').collect());
+
+    let mut renames: Vec<(&str, &str)> = Vec::new();
+    for rename in directive.options("rename") {
+        let rename = rename.split_once(':')
+            .ok_or_else(|| invalid_input(format!(
+                "expected rename=column:Name, got {:?}", rename)))?;
+        renames.push(rename);
+    }
+
+    // only files of the site are published as table
+    let data_path = resolve(ctx.raw_path, data_file)
+        .filter(|rel_path| ctx.tree.entry(rel_path)
+            .is_some_and(|entry| entry.is_file && entry.is_part_of_site()))
+        .map(|rel_path| ctx.tree.in_path().join(rel_path))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!(
+            "table data {:?} is not a file of the site", data_file)))?;
+    ctx.dependencies.file(&data_path);
+    let mut data = String::new();
+    File::open(&data_path)
+        .map_err(|e| io::Error::new(e.kind(), format!(
+            "could not open table data {:?}: {}", data_path, e)))?
+        .read_to_string(&mut data)?;
+
+    let table = match data_path.extension().and_then(|e| e.to_str()) {
+        Some("csv") => parse_csv(&data),
+        Some("json") => parse_json(&data)?,
+        _ => return Err(invalid_input(format!(
+            "unsupported table data {:?}, expected a .csv or .json file",
+            data_path))),
+    };
+
+    // the indices of the columns to show
+    let selected: Vec<usize> = match &columns {
+        Some(columns) => columns.iter()
+            .map(|c| column_index(&table, c))
+            .collect::<io::Result<_>>()?,
+        None => (0..table.header.len()).collect(),
+    };
+
+    let mut rows = table.rows.iter().collect::<Vec<_>>();
+    if let Some(sort) = directive.option("sort") {
+        let i = column_index(&table, sort)?;
+        rows.sort_by(|a, b| compare_cells(
+            a.get(i).map(String::as_str).unwrap_or(""),
+            b.get(i).map(String::as_str).unwrap_or("")));
+    }
+    if directive.flag("reverse") {
+        rows.reverse();
+    }
+
+    file.write_all(r#"</pre>
+<table>
+  <thead>
+    <tr>"#.as_bytes())?;
+
+    for i in &selected {
+        let column = &table.header[*i];
+        let name = renames.iter()
+            .find(|(c, _)| c == column)
+            .map(|(_, name)| *name)
+            .unwrap_or(column);
+        file.write_all(format!(r#"
+      <th>{}</th>"#, escape_html(name)).as_bytes())?;
+    }
+
+    file.write_all(r#"
+    </tr>
+  </thead>
+  <tbody>"#.as_bytes())?;
+
+    for row in rows {
+        file.write_all(r#"
+    <tr>"#.as_bytes())?;
+        for i in &selected {
+            let cell = row.get(*i).map(String::as_str).unwrap_or("");
+            file.write_all(format!(r#"
+      <td>{}</td>"#, escape_html(cell)).as_bytes())?;
+        }
+        file.write_all(r#"
+    </tr>"#.as_bytes())?;
+    }
+
+    file.write_all(r#"
+  </tbody>
+</table>
+<pre>"#.as_bytes())?;
+
+    Ok(())
+}
+
+/// the path of the data file given relative to the page at `raw_path`,
+/// relative to the input path. None if it is absolute or leaves the input
+/// path.
+fn resolve(raw_path: &Path, data_file: &str) -> Option<PathBuf> {
+    let mut rel_path = PathBuf::new();
+    for component in raw_path.join(data_file).components() {
+        match component {
+            Component::ParentDir => {
+                if !rel_path.pop() {
+                    return None;
+                }
+            },
+            Component::Normal(name) => rel_path.push(name),
+            Component::CurDir => {},
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(rel_path)
+}
+
+/// find the index of the column with the given name
+fn column_index(table: &Table, column: &str) -> io::Result<usize> {
+    table.header.iter()
+        .position(|c| c == column)
+        .ok_or_else(|| invalid_input(format!(
+            "unknown table column {:?}, expected one of {:?}",
+            column, table.header)))
+}
+
+/// compare two cells numerically if both are numbers, as strings otherwise
+fn compare_cells(a: &str, b: &str) -> Ordering {
+    match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => a.cmp(b),
+    }
+}
+
+/// parse a csv file with a header line. Fields may be quoted with `"`, quotes
+/// inside quoted fields are escaped by doubling them.
+fn parse_csv(data: &str) -> Table {
+    let mut records: Vec<Vec<String>> = Vec::new();
+    let mut record: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                },
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue
+        }
+
+        match c {
+            '"' => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' => {},
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            },
+            _ => field.push(c),
+        }
+    }
+
+    // the last line might not end with a newline
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    // skip empty lines
+    records.retain(|r| !(r.len() == 1 && r[0].is_empty()));
+
+    let mut records = records.into_iter();
+    Table {
+        header: records.next().unwrap_or_default(),
+        rows: records.collect(),
+    }
+}
+
+/// parse a json array of objects, the columns are the keys of the objects in
+/// the order they first appear in
+fn parse_json(data: &str) -> io::Result<Table> {
+    let value: serde_json::Value = serde_json::from_str(data)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let not_objects = || invalid_input(
+        "table json data must be an array of objects".to_string());
+
+    let objects = value.as_array().ok_or_else(not_objects)?;
+
+    let mut header: Vec<String> = Vec::new();
+    for object in objects {
+        let object = object.as_object().ok_or_else(not_objects)?;
+        for key in object.keys() {
+            if !header.contains(key) {
+                header.push(key.clone());
+            }
+        }
+    }
+
+    let rows = objects.iter()
+        .map(|object| header.iter()
+            .map(|key| match object.get(key) {
+                Some(serde_json::Value::String(s)) => s.clone(),
+                Some(serde_json::Value::Null) | None => String::new(),
+                Some(value) => value.to_string(),
+            })
+            .collect())
+        .collect();
+
+    Ok(Table { header, rows })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_files_in_the_input_path() {
+        let resolve = |raw_path: &str, data_file| resolve(Path::new(raw_path), data_file);
+        assert_eq!(resolve("blog", "data.csv"), Some(PathBuf::from("blog/data.csv")));
+        assert_eq!(resolve("blog", "./sub/../data.csv"),
+            Some(PathBuf::from("blog/data.csv")));
+        assert_eq!(resolve("blog", "../data.json"), Some(PathBuf::from("data.json")));
+        assert_eq!(resolve("blog", "../../secret.csv"), None);
+        assert_eq!(resolve("", "../secret.csv"), None);
+        assert_eq!(resolve("blog", "/etc/passwd.csv"), None);
+    }
+
+    #[test]
+    fn csv_doubled_quotes() {
+        let table = parse_csv("name,quote\nemile,\"say \"\"hi\"\", \"\"bye\"\"\"\n");
+        assert_eq!(table.header, ["name", "quote"]);
+        assert_eq!(table.rows, [["emile", r#"say "hi", "bye""#]]);
+    }
+
+    #[test]
+    fn csv_crlf_line_endings() {
+        let table = parse_csv("a,b\r\n1,2\r\n3,4\r\n");
+        assert_eq!(table.header, ["a", "b"]);
+        assert_eq!(table.rows, [["1", "2"], ["3", "4"]]);
+    }
+
+    #[test]
+    fn csv_missing_trailing_newline() {
+        let table = parse_csv("a,b\n1,2\n3,");
+        assert_eq!(table.rows, [["1", "2"], ["3", ""]]);
+    }
+
+    #[test]
+    fn csv_quoted_newline_and_empty_lines() {
+        let table = parse_csv("a,b\n\n\"x\ny\",2\n\n");
+        assert_eq!(table.rows, [["x\ny", "2"]]);
+    }
+}
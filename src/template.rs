@@ -0,0 +1,162 @@
+/*
+a minimal template engine for the page layout. Templates are html files
+containing `{{ variable }}` placeholders that are replaced with the rendered
+parts of the page. The built-in default template (base.html next to this file)
+produces the classic vokobe layout, it can be replaced by placing a base.html
+in the root of the input path or the theme.
+*/
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::io::{self, Read};
+use std::fs::File;
+
+use crate::theme::Theme;
+
+/// the template used if neither the input nor the theme contain a base.html
+pub const DEFAULT_TEMPLATE: &str = include_str!("base.html");
+
+/// the name of the template file in the input path or theme
+pub const TEMPLATE_FILE: &str = "base.html";
+
+/// the directory in the input path or theme containing the additional templates that
+/// can be selected in the directory settings
+pub const TEMPLATES_DIR: &str = "templates";
+
+/// the variables available in templates
+pub const VARIABLES: &[&str] = &[
+    // the name of the site as given on the command line or in the config
+    "site_name",
+    // the url the site is hosted at
+    "base_url",
+    // the author of the site
+    "author",
+    // the language of the page
+    "lang",
+    // the <link rel="alternate"> elements pointing to the translations of the
+    // page
+    "alternates",
+    // the title of the page from its front matter, the site name otherwise
+    "title",
+    // the content of the style.css
+    "style",
+    // the <link> to the stylesheet, or the inlined <style>
+    "stylesheet",
+    // the header linking to the root of the site
+    "header",
+    // the navigation bar
+    "nav",
+    // the listing of the directories (and files) in the directory of the page
+    "listing",
+    // the rendered README.md
+    "content",
+    // the footer with the icons and the generation info
+    "footer",
+    // the version of vokobe
+    "version",
+];
+
+#[derive(Debug)]
+enum Segment {
+    Text(String),
+    Variable(String),
+}
+
+#[derive(Debug)]
+pub struct Template {
+    segments: Vec<Segment>,
+
+    /// the file the template was loaded from, None for the default template
+    pub path: Option<PathBuf>,
+}
+
+impl Template {
+
+    /// parse the given template source. `name` is used to locate errors.
+    pub fn parse(source: &str, name: &str) -> io::Result<Template> {
+        let mut segments = Vec::new();
+        let mut rest = source;
+
+        while let Some(start) = rest.find("{{") {
+            let line = source.len() - rest.len() + start;
+            let line = source[..line].matches('\n').count() + 1;
+
+            let end = rest[start..].find("}}")
+                .ok_or_else(|| invalid_data(format!(
+                    "{}:{}: unclosed template variable", name, line)))?;
+
+            let variable = rest[start+2..start+end].trim();
+            if !VARIABLES.contains(&variable) {
+                return Err(invalid_data(format!(
+                    "{}:{}: unknown template variable `{}`, expected one of {}",
+                    name, line, variable, VARIABLES.join(", "))));
+            }
+
+            segments.push(Segment::Text(rest[..start].to_string()));
+            segments.push(Segment::Variable(variable.to_string()));
+            rest = &rest[start+end+2..];
+        }
+
+        segments.push(Segment::Text(rest.to_string()));
+
+        Ok(Template { segments, path: None })
+    }
+
+    /// load the base.html from the input path or the theme, falling back to
+    /// the default template
+    pub fn load(theme: &Theme) -> io::Result<Template> {
+        match theme.resolve(Path::new(TEMPLATE_FILE)) {
+            Some(path) => Template::load_file(&path),
+            None => Template::parse(DEFAULT_TEMPLATE, "<default template>"),
+        }
+    }
+
+    /// load the template `templates/<name>.html` from the input path or the
+    /// theme
+    pub fn load_named(theme: &Theme, name: &str) -> io::Result<Template> {
+        let rel_path = Path::new(TEMPLATES_DIR).join(name).with_extension("html");
+
+        match theme.resolve(&rel_path) {
+            Some(path) => Template::load_file(&path),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, format!(
+                "could not open template {:?}: not found in the input path \
+                or the theme", rel_path))),
+        }
+    }
+
+    fn load_file(path: &Path) -> io::Result<Template> {
+        let mut source = String::new();
+        File::open(path)
+            .map_err(|e| io::Error::new(e.kind(), format!(
+                "could not open template {:?}: {}", path, e)))?
+            .read_to_string(&mut source)?;
+
+        let mut template = Template::parse(&source, &path.display().to_string())?;
+        template.path = Some(path.to_path_buf());
+
+        Ok(template)
+    }
+
+    /// render the template, replacing the variables with the given values.
+    /// Variables without a value are replaced with nothing.
+    pub fn render(&self, variables: &BTreeMap<&str, String>) -> String {
+        let mut output = String::new();
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => output.push_str(text),
+                Segment::Variable(name) => {
+                    if let Some(value) = variables.get(name.as_str()) {
+                        output.push_str(value);
+                    }
+                },
+            }
+        }
+
+        output
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
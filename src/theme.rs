@@ -0,0 +1,119 @@
+/*
+themes: a directory containing the templates (base.html, templates/), the
+stylesheets and other assets (icons, fonts, ...) of a site. Every file in the
+input path overrides the file with the same path in the theme, so that sites
+sharing a theme can still tweak single files. Without a theme, the built-in
+default template is used.
+*/
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::io;
+use std::fs;
+
+use crate::template;
+use crate::tree::SiteTree;
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    in_path: PathBuf,
+    dir: Option<PathBuf>,
+
+    /// all non-hidden files of the theme relative to its directory
+    files: BTreeSet<PathBuf>,
+}
+
+impl Theme {
+
+    /// the theme in the given directory, or no theme at all
+    pub fn new(in_path: &Path, dir: Option<&Path>) -> io::Result<Theme> {
+        if let Some(dir) = dir {
+            if !dir.is_dir() {
+                return Err(io::Error::new(io::ErrorKind::NotFound,
+                    format!("theme directory {:?} does not exist", dir)));
+            }
+        }
+
+        let mut files = Vec::new();
+        if let Some(dir) = dir {
+            collect_files(dir, dir, &mut files)?;
+        }
+
+        Ok(Theme {
+            in_path: in_path.to_path_buf(),
+            dir: dir.map(Path::to_path_buf),
+            files: files.into_iter().collect(),
+        })
+    }
+
+    /// the file at the given path relative to the input path: the one in the
+    /// input path if it exists, the one in the theme otherwise
+    pub fn resolve(&self, rel_path: &Path) -> Option<PathBuf> {
+        let path = self.in_path.join(rel_path);
+        if path.is_file() {
+            return Some(path);
+        }
+
+        self.dir.as_ref()
+            .map(|dir| dir.join(rel_path))
+            .filter(|path| path.is_file())
+    }
+
+    /// whether `resolve` finds a (non-hidden) file at the given path,
+    /// answered from the site tree and the files of the theme
+    pub fn has_file(&self, tree: &SiteTree, rel_path: &Path) -> bool {
+        tree.entry(rel_path).is_some_and(|entry| entry.is_file)
+            || self.files.contains(rel_path)
+    }
+
+    /// whether the given path is part of the theme directory
+    pub fn contains(&self, path: &Path) -> bool {
+        match &self.dir {
+            Some(dir) => path.starts_with(dir),
+            None => false,
+        }
+    }
+
+    /// the assets of the theme that have to be copied to the output: all
+    /// files except for the templates and hidden files, that are not
+    /// overridden by a file in the input path. Returns the pathes relative
+    /// to the theme directory.
+    pub fn assets(&self) -> io::Result<Vec<PathBuf>> {
+        let assets = self.files.iter()
+            .filter(|rel_path| {
+                rel_path.as_path() != Path::new(template::TEMPLATE_FILE)
+                    && !rel_path.starts_with(template::TEMPLATES_DIR)
+                    && !self.in_path.join(rel_path).exists()
+            })
+            .cloned()
+            .collect();
+
+        Ok(assets)
+    }
+
+    /// the path of the theme asset with the given relative path
+    pub fn asset_path(&self, rel_path: &Path) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(rel_path))
+    }
+}
+
+/// collect all non-hidden files below `dir` relative to `root`
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>)
+    -> io::Result<()> {
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.file_name().unwrap().to_str().unwrap().starts_with('.') {
+            continue
+        }
+
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            files.push(path.strip_prefix(root).unwrap().to_path_buf());
+        }
+    }
+
+    Ok(())
+}
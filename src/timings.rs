@@ -0,0 +1,113 @@
+/*
+build timings (`--timings`): how long each phase of the build and the
+rendering of each page took, to find the pages making a build slow (usually
+ones with a big tree or gallery). A summary is printed at the end of the
+build, the full report is written as json next to the output path.
+*/
+
+use std::path::{Path, PathBuf};
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// the amount of pages listed in the summary
+const SLOWEST_PAGES: usize = 10;
+
+#[derive(Debug, Serialize)]
+struct Phase {
+    name: String,
+    seconds: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct PageTiming {
+    /// the README the page is rendered from, relative to the input path
+    page: PathBuf,
+    seconds: f64,
+
+    /// false if the page was unchanged and only checked against the cache
+    rendered: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Timings {
+    #[serde(skip)]
+    start: Instant,
+    #[serde(skip)]
+    last: Instant,
+
+    total: f64,
+    phases: Vec<Phase>,
+
+    /// the pages, slowest first. They are rendered in parallel, so their
+    /// times add up to more than the rendering phase.
+    pages: Vec<PageTiming>,
+}
+
+impl Timings {
+
+    /// start timing the first phase
+    pub fn new() -> Timings {
+        let now = Instant::now();
+        Timings {
+            start: now,
+            last: now,
+            total: 0.0,
+            phases: Vec::new(),
+            pages: Vec::new(),
+        }
+    }
+
+    /// end the current phase, the next one starts now
+    pub fn phase(&mut self, name: &str) {
+        let now = Instant::now();
+        self.phases.push(Phase {
+            name: name.to_string(),
+            seconds: (now - self.last).as_secs_f64(),
+        });
+        self.last = now;
+        self.total = (now - self.start).as_secs_f64();
+    }
+
+    /// record how long the page took to render (or to check, if unchanged)
+    pub fn page(&mut self, page: &Path, duration: Duration, rendered: bool) {
+        let seconds = duration.as_secs_f64();
+
+        // keep the pages sorted, pages taking the same time in the order
+        // they were recorded in
+        let index = self.pages.partition_point(|page| page.seconds >= seconds);
+        self.pages.insert(index, PageTiming {
+            page: page.to_path_buf(),
+            seconds,
+            rendered,
+        });
+    }
+
+    /// print the phases and the slowest pages
+    pub fn print_summary(&self) {
+        println!("Timings:");
+        for phase in &self.phases {
+            println!("  {:<10} {:>9.3}s", phase.name, phase.seconds);
+        }
+        println!("  {:<10} {:>9.3}s", "total", self.total);
+
+        if self.pages.is_empty() {
+            return;
+        }
+
+        println!("Slowest pages:");
+        for page in self.pages.iter().take(SLOWEST_PAGES) {
+            println!("  {:>9.3}s {}{}", page.seconds, page.page.display(),
+                if page.rendered { "" } else { " (unchanged)" });
+        }
+    }
+
+    /// write the full report as json to the given path
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        crate::create_file(path)?.write_all(json.as_bytes())
+    }
+}
@@ -0,0 +1,246 @@
+/*
+the site tree: the input path is scanned once per build, all render functions
+query this snapshot instead of reading the filesystem again. It contains the
+entries of all directories that are part of the site, the content of all
+README.md files (and their translations) and the files configuring the
+directories: the settings files and the gallery captions.
+*/
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::io::{self, Read};
+use std::fs::{self, File};
+use std::time::SystemTime;
+
+use crate::config::Config;
+use crate::frontmatter::{self, FrontMatter};
+use crate::gallery;
+use crate::gitignore_entries;
+use crate::i18n;
+use crate::settings::{self, SettingsFile};
+
+#[derive(Debug)]
+pub struct Entry {
+    /// the path of the entry, starting with the input path
+    pub path: PathBuf,
+    pub name: String,
+    pub is_dir: bool,
+
+    /// a file or a symlink to one, false for broken symlinks
+    pub is_file: bool,
+
+    pub size: u64,
+    pub modified: SystemTime,
+
+    /// excluded by the .gitignore in its directory
+    pub gitignored: bool,
+
+    /// excluded by the ignore patterns from the config
+    pub ignored: bool,
+}
+
+impl Entry {
+
+    /// whether the entry is part of the site, its directories are scanned
+    pub fn is_part_of_site(&self) -> bool {
+        !self.gitignored && !self.ignored
+    }
+}
+
+#[derive(Debug)]
+pub struct SiteTree {
+    in_path: PathBuf,
+
+    /// the latest modification time, see `scan`
+    source_date: Option<SystemTime>,
+
+    modified: SystemTime,
+
+    /// the entries of all scanned directories sorted by name, by the path of
+    /// the directory relative to the input path
+    dirs: BTreeMap<PathBuf, Vec<Entry>>,
+
+    /// the content of all README.md files and their translations, by their
+    /// path relative to the input path
+    readmes: BTreeMap<PathBuf, String>,
+
+    /// the settings file of each directory that has one, by the path of the
+    /// directory relative to the input path
+    settings_files: BTreeMap<PathBuf, SettingsFile>,
+
+    /// the content of the captions file of each directory that has one, by
+    /// the path of the directory relative to the input path
+    captions: BTreeMap<PathBuf, String>,
+}
+
+impl SiteTree {
+
+    /// scan the input path. Modification times later than `source_date` are
+    /// clamped to it, so that the output doesn't depend on when the input
+    /// was checked out.
+    pub fn scan(in_path: &Path, config: &Config, source_date: Option<SystemTime>)
+        -> io::Result<SiteTree> {
+
+        let mut tree = SiteTree {
+            in_path: in_path.to_path_buf(),
+            source_date,
+            modified: in_path.metadata()?.modified()?,
+            dirs: BTreeMap::new(),
+            readmes: BTreeMap::new(),
+            settings_files: BTreeMap::new(),
+            captions: BTreeMap::new(),
+        };
+        tree.modified = tree.clamp(tree.modified);
+
+        tree.scan_dir(Path::new(""), config)?;
+
+        Ok(tree)
+    }
+
+    fn scan_dir(&mut self, rel_dir: &Path, config: &Config) -> io::Result<()> {
+        let dir = self.in_path.join(rel_dir);
+        let gitignore_entries = gitignore_entries(&dir)?;
+
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+
+            // broken symlinks are kept as entries of their own
+            let metadata = match path.metadata() {
+                Ok(metadata) => metadata,
+                Err(_) => path.symlink_metadata()?,
+            };
+
+            let gitignored = gitignore_entries.iter().any(|gitignore_entry| {
+                gitignore_entry.to_str() != Some("")
+                    && path.ends_with(gitignore_entry)
+            });
+
+            entries.push(Entry {
+                name: path.file_name().unwrap().to_str().unwrap().to_string(),
+                is_dir: metadata.is_dir(),
+                is_file: metadata.is_file(),
+                size: metadata.len(),
+                modified: self.clamp(metadata.modified()?),
+                gitignored,
+                ignored: config.is_ignored(&path),
+                path,
+            });
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for entry in &entries {
+            // the settings and captions apply even if they are ignored
+            if !entry.is_dir && entry.name == settings::SETTINGS_FILE {
+                self.settings_files.insert(rel_dir.to_path_buf(),
+                    SettingsFile::read(&entry.path)?);
+            } else if !entry.is_dir && entry.name == gallery::CAPTIONS_FILE {
+                let mut captions = String::new();
+                File::open(&entry.path)?.read_to_string(&mut captions)?;
+                self.captions.insert(rel_dir.to_path_buf(), captions);
+            }
+
+            if !entry.is_part_of_site() {
+                continue
+            }
+
+            if entry.is_dir {
+                self.scan_dir(&rel_dir.join(&entry.name), config)?;
+            } else if i18n::is_readme(&entry.name) {
+                let mut readme = String::new();
+                File::open(&entry.path)?.read_to_string(&mut readme)?;
+                self.readmes.insert(rel_dir.join(&entry.name), readme);
+            }
+        }
+
+        self.dirs.insert(rel_dir.to_path_buf(), entries);
+
+        Ok(())
+    }
+
+    fn clamp(&self, time: SystemTime) -> SystemTime {
+        match self.source_date {
+            Some(source_date) => time.min(source_date),
+            None => time,
+        }
+    }
+
+    pub fn in_path(&self) -> &Path {
+        &self.in_path
+    }
+
+    /// the modification time of the input path
+    pub fn modified(&self) -> SystemTime {
+        self.modified
+    }
+
+    /// all entries of the directory (relative to the input path), including
+    /// the ones not part of the site. Empty if the directory wasn't scanned.
+    pub fn entries(&self, rel_dir: &Path) -> &[Entry] {
+        self.dirs.get(rel_dir).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// whether the directory (relative to the input path) was scanned, that
+    /// is it exists and is part of the site
+    pub fn is_dir(&self, rel_dir: &Path) -> bool {
+        self.dirs.contains_key(rel_dir)
+    }
+
+    /// the entry at the given path relative to the input path
+    pub fn entry(&self, rel_path: &Path) -> Option<&Entry> {
+        let name = rel_path.file_name()?.to_str()?;
+        self.entries(rel_path.parent()?)
+            .iter()
+            .find(|entry| entry.name == name)
+    }
+
+    /// the pathes of all files and directories in the directory and its
+    /// subdirectories that are part of the site, parents before their
+    /// children
+    pub fn pathes(&self, rel_dir: &Path, dir_only: bool) -> Vec<PathBuf> {
+        let mut pathes = Vec::new();
+
+        for entry in self.entries(rel_dir) {
+            if !entry.is_part_of_site() || (dir_only && !entry.is_dir) {
+                continue
+            }
+
+            pathes.push(entry.path.clone());
+            if entry.is_dir {
+                pathes.extend(self.pathes(&rel_dir.join(&entry.name), dir_only));
+            }
+        }
+
+        pathes
+    }
+
+    /// the content of the README file at the given path relative to the
+    /// input path
+    pub fn readme(&self, rel_path: &Path) -> Option<&str> {
+        self.readmes.get(rel_path).map(String::as_str)
+    }
+
+    /// all README files and their content
+    pub fn readmes(&self) -> impl Iterator<Item = (&PathBuf, &String)> {
+        self.readmes.iter()
+    }
+
+    /// the settings file in the given directory, if there is one
+    pub fn settings_file(&self, rel_dir: &Path) -> Option<&SettingsFile> {
+        self.settings_files.get(rel_dir)
+    }
+
+    /// the content of the captions file in the given directory, if there is
+    /// one
+    pub fn captions(&self, rel_dir: &Path) -> Option<&str> {
+        self.captions.get(rel_dir).map(String::as_str)
+    }
+
+    /// the front matter of the README.md in the given directory, empty if
+    /// there is none
+    pub fn front_matter(&self, rel_dir: &Path) -> FrontMatter {
+        self.readme(&rel_dir.join("README.md"))
+            .map(|readme| frontmatter::split(readme).0)
+            .unwrap_or_default()
+    }
+}
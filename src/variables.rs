@@ -0,0 +1,125 @@
+/*
+variables in the content: `{{ page.title }}`, `{{ site.name }}`, ... in a
+README.md are replaced with their value when rendering the page. A `\{{` is
+written as a literal `{{`, code lines aren't substituted at all. The available
+variables are:
+
+    site.name, site.base_url, site.author, site.lang
+    page.title, page.updated, page.path, page.lang
+    page.<key>     any key from the front matter of the page
+    extra.<key>    any key from the `[extra]` table in the config
+*/
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::config::Config;
+use crate::frontmatter::FrontMatter;
+use crate::i18n;
+use crate::{escape_html, format_date};
+
+#[derive(Debug)]
+pub struct Variables {
+    values: BTreeMap<String, String>,
+}
+
+impl Variables {
+
+    /// the variables of the page at `raw_path` rendered from the README.md at
+    /// `readme_path` (last modified at `modified`) with the given front matter
+    pub fn for_page(config: &Config, raw_path: &Path, readme_path: &Path,
+        modified: SystemTime, front_matter: &FrontMatter) -> Variables {
+
+        let mut values = BTreeMap::new();
+
+        let site_name = config.name.clone().unwrap_or_default();
+        values.insert("site.name".to_string(), site_name.clone());
+        values.insert("site.base_url".to_string(),
+            config.base_url.clone().unwrap_or_default());
+        values.insert("site.author".to_string(),
+            config.author.clone().unwrap_or_default());
+        values.insert("site.lang".to_string(), config.language().to_string());
+
+        for (key, value) in &config.extra {
+            let value = match value {
+                toml::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            values.insert(format!("extra.{}", key), value);
+        }
+
+        for (key, value) in front_matter.iter() {
+            values.insert(format!("page.{}", key), value.to_string());
+        }
+
+        // the title and update date default to the site name and the
+        // modification time of the README.md
+        values.insert("page.title".to_string(),
+            front_matter.get("title").unwrap_or(&site_name).to_string());
+        let updated = match front_matter.get("updated") {
+            Some(updated) => updated.to_string(),
+            None => format_date(modified),
+        };
+        values.insert("page.updated".to_string(), updated);
+
+        // translations are rendered into a tree prefixed with their language
+        let translation = readme_path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(i18n::readme_language)
+            .flatten();
+        values.insert("page.lang".to_string(),
+            translation.unwrap_or(config.language()).to_string());
+
+        let mut page_path = Path::new("/").to_path_buf();
+        if let Some(lang) = translation {
+            page_path.push(lang);
+        }
+        page_path.push(raw_path);
+        let path = match page_path.to_str().unwrap() {
+            "/" => "/".to_string(),
+            path => format!("{}/", path),
+        };
+        values.insert("page.path".to_string(), path);
+
+        Variables { values }
+    }
+
+    /// replace the variables in the given line with their (escaped) values
+    pub fn substitute(&self, line: &str) -> Result<String, String> {
+        let mut output = String::new();
+        let mut rest = line;
+
+        while let Some(start) = rest.find("{{") {
+
+            // an escaped `\{{` is kept as literal `{{`
+            if rest[..start].ends_with('\\') {
+                output.push_str(&rest[..start-1]);
+                output.push_str("{{");
+                rest = &rest[start+2..];
+                continue
+            }
+
+            let end = rest[start..].find("}}")
+                .ok_or_else(|| "unclosed variable, use `\\{{` for literal \
+                    braces".to_string())?;
+
+            let name = rest[start+2..start+end].trim();
+            let value = self.values.get(name)
+                .ok_or_else(|| format!("unknown variable `{}`, expected one \
+                    of {}", name, self.names().join(", ")))?;
+
+            output.push_str(&rest[..start]);
+            output.push_str(&escape_html(value));
+            rest = &rest[start+end+2..];
+        }
+
+        output.push_str(rest);
+
+        Ok(output)
+    }
+
+    fn names(&self) -> Vec<&str> {
+        self.values.keys().map(String::as_str).collect()
+    }
+}